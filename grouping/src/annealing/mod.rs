@@ -1,162 +1,105 @@
-pub mod analysis;
-mod group_move;
-pub mod http;
-mod objective;
-mod temperature;
-
-use crate::annealing::objective::objective;
-use rand::Rng;
-use std::{
-    collections::HashMap,
-    ops::{Deref, DerefMut},
-};
+pub mod temperature;
+
+use rand::{Rng, RngCore};
+use std::time::Instant;
 use tracing::debug;
 
-use self::analysis::get_violations;
+use self::temperature::{CoolingSchedule, Schedule};
+use crate::solver::{
+    diagnosis, evaluation::State, finish, good_enough_score, group_move, Problem, Report, RunLimit,
+    Solver,
+};
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
-pub struct StudentId(usize);
-impl std::fmt::Display for StudentId {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-impl Deref for StudentId {
-    type Target = usize;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-impl DerefMut for StudentId {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-impl From<usize> for StudentId {
-    fn from(id: usize) -> Self {
-        StudentId(id)
-    }
+/// Searches for a good grouping, cooling linearly from `max_temp` to `min_temp`. All randomness
+/// comes from `rng`, so a seeded generator gives the same result for the same problem.
+pub fn simulated_annealing(
+    problem: &Problem,
+    max_temp: f64,
+    min_temp: f64,
+    steps: i32,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Report> {
+    let mut schedule = Schedule::Linear.build(max_temp, min_temp)?;
+    simulated_annealing_with_schedule(problem, schedule.as_mut(), RunLimit::steps(steps), rng)
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
-pub struct GroupId(usize);
-impl std::fmt::Display for GroupId {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-impl Deref for GroupId {
-    type Target = usize;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-impl DerefMut for GroupId {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-impl From<usize> for GroupId {
-    fn from(id: usize) -> Self {
-        GroupId(id)
-    }
+/// Simulated annealing, cooling from `max_temp` to `min_temp` by the schedule.
+#[derive(Debug, Clone)]
+pub struct Annealing {
+    pub schedule: Schedule,
+    pub max_temp: f64,
+    pub min_temp: f64,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
-pub struct RelationshipPair {
-    first_student_id: StudentId,
-    second_student_id: StudentId,
+impl Solver for Annealing {
+    fn solve(
+        &self,
+        problem: &Problem,
+        limit: RunLimit,
+        mut rng: &mut dyn RngCore,
+    ) -> anyhow::Result<Report> {
+        let mut schedule = self.schedule.build(self.max_temp, self.min_temp)?;
+        simulated_annealing_with_schedule(problem, schedule.as_mut(), limit, &mut rng)
+    }
 }
 
-pub type Solution = HashMap<StudentId, GroupId>;
-
-pub type Groups = Vec<Vec<StudentId>>;
-
-#[derive(Debug, serde::Serialize)]
-pub struct Violation {
-    pub relationship_pair: RelationshipPair,
-    pub group: GroupId,
-}
+/// Searches for a good grouping, taking the temperature of each step from the schedule. The
+/// run ends at its limit or as soon as the lowest possible objective is reached, returning the
+/// best grouping found so far.
+#[tracing::instrument(skip(rng))]
+pub fn simulated_annealing_with_schedule(
+    problem: &Problem,
+    schedule: &mut dyn CoolingSchedule,
+    limit: RunLimit,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Report> {
+    diagnosis::diagnose(problem)?;
+    let start = Instant::now();
+    let lower_bound = good_enough_score(problem);
+    let mut state = State::new(problem, &group_move::random_solution(problem, rng)?)?;
 
-pub struct AnnealingResult {
-    pub groups: Groups,
-    pub violations: Vec<Violation>,
-    pub objective: f64,
-}
+    let mut best_assignment = state.assignment().to_vec();
+    let mut best_score = state.score();
 
-impl std::fmt::Display for AnnealingResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "Objective: {}", self.objective)?;
-        writeln!(f, "Violations:")?;
-        for violation in &self.violations {
-            writeln!(
-                f,
-                "Students {} and {} in group {}",
-                violation.relationship_pair.first_student_id,
-                violation.relationship_pair.second_student_id,
-                violation.group
-            )?;
-        }
-        writeln!(f, "Groups:")?;
-        for (i, group) in self.groups.iter().enumerate() {
-            writeln!(f, "Group {}: {:?}", i, group)?;
+    let mut step = 0;
+    while let Some(progress) = limit.progress(step, start) {
+        if best_score <= lower_bound {
+            debug!("Reached the lower bound after {} steps", step);
+            break;
         }
-        Ok(())
-    }
-}
+        let temp = schedule.temperature(progress);
 
-#[tracing::instrument]
-pub fn simulated_annealing(
-    students: Vec<StudentId>,
-    num_groups: usize,
-    restrictions: &[RelationshipPair],
-    max_temp: f64,
-    min_temp: f64,
-    steps: i32,
-) -> anyhow::Result<AnnealingResult> {
-    let mut rng = rand::thread_rng();
-    let mut solution: Solution = students
-        .into_iter()
-        .map(|student| (student, rng.gen_range(0..num_groups).into()))
-        .collect();
-
-    let mut best_solution = solution.clone();
-    let mut best_score = objective(&solution, num_groups, restrictions)?;
-
-    for step in 0..steps {
-        let temp = temperature::change_temp(max_temp, min_temp, steps, step)?;
-
-        let new_solution = group_move::make_move(&solution, num_groups)?;
-        let new_score = objective(&new_solution, num_groups, restrictions)?;
-
-        if new_score < best_score || rng.gen::<f64>() < ((best_score - new_score) / temp).exp() {
-            solution = new_solution;
-            if new_score < best_score {
-                best_solution = solution.clone();
-                best_score = new_score;
-            }
-        }
-    }
-    let result = AnnealingResult {
-        groups: create_group_list(&best_solution, num_groups),
-        violations: get_violations(&best_solution, restrictions),
-        objective: best_score,
-    };
-    debug!("Result: {}", result);
-    Ok(result)
-}
+        // the move is made in place and taken back if it is not accepted
+        let (inverse, delta) = group_move::make_move(&mut state, problem, rng)?;
+        let new_score = state.score();
 
-fn create_group_list(solution: &Solution, num_groups: usize) -> Vec<Vec<StudentId>> {
-    let mut groups: Vec<Vec<StudentId>> = vec![vec![]; num_groups];
-    for (student, group) in solution {
-        groups[**group].push(student.clone());
+        let accepted =
+            new_score < best_score || rng.gen::<f64>() < ((best_score - new_score) / temp).exp();
+        schedule.record(accepted);
+        if !accepted {
+            state.undo(&inverse, delta);
+        } else if new_score < best_score {
+            best_assignment.copy_from_slice(state.assignment());
+            best_score = new_score;
+        }
+        step += 1;
     }
-    groups
+    finish(problem, &state.solution_of(&best_assignment))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::annealing::{analysis::get_violations, group_move::all_equal};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashMap;
+
+    use crate::solver::{
+        constraints::{Coverage, SpreadSet},
+        diagnosis::Conflict,
+        group_move::within_bounds,
+        preferences::{Guarantee, Preference, Preferences},
+        problem::{even_group_sizes, Pin, SizeBounds},
+        RelationshipKind, RelationshipPair, StudentId,
+    };
 
     use super::*;
 
@@ -177,6 +120,9 @@ mod tests {
                 RelationshipPair {
                     first_student_id,
                     second_student_id,
+                    kind: RelationshipKind::Apart,
+                    weight: 1.0,
+                    hard: false,
                 }
             })
             .collect()
@@ -184,24 +130,19 @@ mod tests {
 
     #[test]
     fn test_annealing() {
-        let students: Vec<StudentId> = (0..21).map(StudentId).collect();
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
         for _ in 0..1000 {
             let restrictions = generate_random_restriction_pairs(5, 21);
             let num_groups = 4;
+            let problem = Problem::new(students.clone(), num_groups, restrictions.clone());
             let result =
-                simulated_annealing(students.clone(), num_groups, &restrictions, 10.0, 0.1, 1000)
-                    .unwrap();
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
             if !result.violations.is_empty() {
                 println!("{:?}", result.groups);
                 println!("Restrictions: {:?}", restrictions);
                 println!("Violations:");
                 for violation in result.violations {
-                    println!(
-                        "Students {} and {} in group {}",
-                        violation.relationship_pair.first_student_id,
-                        violation.relationship_pair.second_student_id,
-                        violation.group
-                    );
+                    println!("{}", violation);
                 }
             }
             let group_sizes = result
@@ -209,7 +150,255 @@ mod tests {
                 .iter()
                 .map(|group| group.len())
                 .collect::<Vec<_>>();
-            assert!(all_equal(num_groups, students.len(), &group_sizes));
+            assert!(within_bounds(
+                &group_sizes,
+                &even_group_sizes(students.len(), num_groups)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_annealing_large_class() {
+        let num_students = 1200;
+        let students: Vec<StudentId> = (0..num_students).map(StudentId::from).collect();
+        let restrictions = generate_random_restriction_pairs(300, num_students);
+        let problem = Problem::new(students, 48, restrictions);
+        let result =
+            simulated_annealing(&problem, 10.0, 0.1, 100_000, &mut StdRng::seed_from_u64(1))
+                .unwrap();
+        assert!(result.violations.is_empty());
+        let group_sizes: Vec<usize> = result.groups.iter().map(Vec::len).collect();
+        assert!(within_bounds(
+            &group_sizes,
+            &even_group_sizes(num_students, 48)
+        ));
+    }
+
+    #[test]
+    fn test_annealing_seeded() {
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
+        let restrictions = generate_random_restriction_pairs(20, 21);
+        let problem = Problem::new(students, 4, restrictions);
+        let solve = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rng).unwrap()
+        };
+        let (first, second) = (solve(7), solve(7));
+        assert_eq!(first.groups, second.groups);
+        assert_eq!(first.objective, second.objective);
+    }
+
+    #[test]
+    fn test_annealing_hard_constraints() {
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
+        for _ in 0..100 {
+            let restrictions: Vec<RelationshipPair> = generate_random_restriction_pairs(5, 21)
+                .into_iter()
+                .map(|pair| RelationshipPair { hard: true, ..pair })
+                .collect();
+            let problem = Problem::new(students.clone(), 4, restrictions);
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.violations.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_annealing_infeasible_hard_constraints() {
+        let students: Vec<StudentId> = (0..10).map(StudentId::from).collect();
+        // five students who must all be apart cannot fit into four groups
+        let mut restrictions = vec![];
+        for first in 0..5 {
+            for second in first + 1..5 {
+                restrictions.push(RelationshipPair {
+                    first_student_id: first.into(),
+                    second_student_id: second.into(),
+                    kind: RelationshipKind::Apart,
+                    weight: 1.0,
+                    hard: true,
+                });
+            }
+        }
+        let problem = Problem::new(students, 4, restrictions);
+        let error = simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng())
+            .err()
+            .unwrap();
+        // the conflict is found before annealing starts
+        let conflict = error.downcast_ref::<Conflict>().unwrap();
+        assert_eq!(conflict.students.len(), 5);
+    }
+
+    #[test]
+    fn test_annealing_together_units() {
+        let students: Vec<StudentId> = (0..20).map(StudentId::from).collect();
+        // a chain of three and a pair that must be together, and must be apart from each other
+        let pair = |first: usize, second: usize, kind| RelationshipPair {
+            first_student_id: first.into(),
+            second_student_id: second.into(),
+            kind,
+            weight: 1.0,
+            hard: true,
+        };
+        let restrictions = vec![
+            pair(0, 7, RelationshipKind::Together),
+            pair(7, 13, RelationshipKind::Together),
+            pair(2, 5, RelationshipKind::Together),
+            pair(13, 5, RelationshipKind::Apart),
+        ];
+        let problem = Problem::new(students, 4, restrictions);
+        assert_eq!(problem.units.len(), 17);
+        for _ in 0..10 {
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.violations.is_empty());
+            let group_of = |student: usize| {
+                result
+                    .groups
+                    .iter()
+                    .position(|group| group.contains(&StudentId::from(student)))
+                    .unwrap()
+            };
+            assert_eq!(group_of(0), group_of(13));
+            assert_eq!(group_of(2), group_of(5));
+        }
+    }
+
+    #[test]
+    fn test_annealing_explicit_group_sizes() {
+        let students: Vec<StudentId> = (0..16).map(StudentId::from).collect();
+        let group_sizes = vec![
+            SizeBounds::exact(6),
+            SizeBounds::exact(4),
+            SizeBounds::exact(4),
+            SizeBounds { min: 1, max: 2 },
+        ];
+        for _ in 0..100 {
+            let restrictions = generate_random_restriction_pairs(5, 16);
+            let problem = Problem::new(students.clone(), 4, restrictions)
+                .with_group_sizes(group_sizes.clone())
+                .unwrap();
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            let sizes = result
+                .groups
+                .iter()
+                .map(|group| group.len())
+                .collect::<Vec<_>>();
+            assert!(within_bounds(&sizes, &group_sizes));
+        }
+    }
+
+    #[test]
+    fn test_annealing_pinned_students() {
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
+        let pins = vec![
+            Pin {
+                student_id: 0.into(),
+                group: Some(2.into()),
+                forbidden_groups: vec![],
+            },
+            Pin {
+                student_id: 1.into(),
+                group: None,
+                forbidden_groups: vec![0.into(), 1.into(), 2.into()],
+            },
+        ];
+        for _ in 0..100 {
+            let restrictions = generate_random_restriction_pairs(5, 21);
+            let problem = Problem::new(students.clone(), 4, restrictions)
+                .with_pins(&pins)
+                .unwrap();
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.groups[2].contains(&0.into()));
+            assert!(result.groups[3].contains(&1.into()));
+        }
+
+        let out_of_range = Pin {
+            student_id: 0.into(),
+            group: None,
+            forbidden_groups: vec![4.into()],
+        };
+        let error = Problem::new(students, 4, vec![])
+            .with_pins(&[out_of_range])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("forbidden from group 4"));
+    }
+
+    #[test]
+    fn test_annealing_spread_sets() {
+        let students: Vec<StudentId> = (0..20).map(StudentId::from).collect();
+        let spread_set = SpreadSet {
+            students: (0..8).map(StudentId::from).collect(),
+            max_per_group: 2,
+            weight: 1.0,
+            hard: true,
+        };
+        for _ in 0..100 {
+            let problem = Problem::new(students.clone(), 4, vec![])
+                .with_spread_sets(vec![spread_set.clone()])
+                .unwrap();
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.violations.is_empty());
+            for group in &result.groups {
+                let members = group.iter().filter(|student| ***student < 8).count();
+                assert_eq!(members, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_annealing_coverage() {
+        let students: Vec<StudentId> = (0..20).map(StudentId::from).collect();
+        let tags = HashMap::from([(
+            "certified".to_string(),
+            (0..4).map(StudentId::from).collect::<Vec<_>>(),
+        )]);
+        let coverage = Coverage {
+            tag: "certified".to_string(),
+            min_per_group: 1,
+            weight: 1.0,
+            hard: true,
+        };
+        for _ in 0..100 {
+            let problem = Problem::new(students.clone(), 4, vec![])
+                .with_coverage(tags.clone(), vec![coverage.clone()])
+                .unwrap();
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.violations.is_empty());
+            for group in &result.groups {
+                assert_eq!(group.iter().filter(|student| ***student < 4).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_annealing_no_isolated_students() {
+        let students: Vec<StudentId> = (0..20).map(StudentId::from).collect();
+        // everyone wishes to work with their neighbours in a circle
+        let preferences = Preferences {
+            students: (0..20)
+                .map(|student| Preference {
+                    student_id: student.into(),
+                    wishes: vec![((student + 1) % 20).into(), ((student + 19) % 20).into()],
+                })
+                .collect(),
+            weight: 0.0,
+            guarantee: Some(Guarantee {
+                weight: 1.0,
+                hard: true,
+            }),
+        };
+        for _ in 0..10 {
+            let problem = Problem::new(students.clone(), 4, vec![])
+                .with_preferences(preferences.clone())
+                .unwrap();
+            let result =
+                simulated_annealing(&problem, 10.0, 0.1, 1000, &mut rand::thread_rng()).unwrap();
+            assert!(result.violations.is_empty());
         }
     }
 }
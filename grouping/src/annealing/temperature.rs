@@ -1,11 +1,225 @@
-pub fn change_temp(max_temp: f64, min_temp: f64, steps: i32, step: i32) -> anyhow::Result<f64> {
-    linear_change_temp(max_temp, min_temp, steps, step)
+/// How many moves the adaptive schedule looks at before adjusting the temperature.
+const ADAPTIVE_WINDOW: usize = 20;
+/// How much the adaptive schedule cools or heats after each window.
+const ADAPTIVE_FACTOR: f64 = 0.9;
+
+fn default_target_acceptance() -> f64 {
+    0.5
+}
+
+/// Decides the temperature at each step of the annealing. Implement this to try out a schedule
+/// of your own with [`simulated_annealing_with_schedule`](super::simulated_annealing_with_schedule).
+pub trait CoolingSchedule: std::fmt::Debug {
+    /// The temperature once `progress` of the run is done, going from 0 at the start to 1 at the
+    /// end, measured in steps or in time.
+    fn temperature(&mut self, progress: f64) -> f64;
+
+    /// Called after each step with whether the proposed move was accepted.
+    fn record(&mut self, _accepted: bool) {}
+}
+
+/// The cooling schedules that can be chosen in a request.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Schedule {
+    #[default]
+    Linear,
+    /// Multiplies the temperature by the same factor each step.
+    Geometric,
+    /// Cools quickly at first and then ever more slowly.
+    Logarithmic,
+    /// Cools while more moves are accepted than the target and heats up while fewer are. The
+    /// target falls to zero over the run.
+    Adaptive {
+        #[serde(default = "default_target_acceptance")]
+        target_acceptance: f64,
+    },
+    /// Runs the inner schedule `cycles` times, heating back up to the maximum temperature at the
+    /// start of each cycle.
+    Reheating {
+        cycles: u32,
+        schedule: Box<Schedule>,
+    },
+}
+
+impl Schedule {
+    pub fn build(&self, max_temp: f64, min_temp: f64) -> anyhow::Result<Box<dyn CoolingSchedule>> {
+        if !min_temp.is_finite() || min_temp <= 0.0 || !max_temp.is_finite() || max_temp < min_temp
+        {
+            return Err(anyhow::anyhow!(
+                "Temperatures must be positive with the maximum {} at least the minimum {}",
+                max_temp,
+                min_temp
+            ));
+        }
+        let schedule: Box<dyn CoolingSchedule> = match self {
+            Schedule::Linear => Box::new(Linear { max_temp, min_temp }),
+            Schedule::Geometric => Box::new(Geometric { max_temp, min_temp }),
+            Schedule::Logarithmic => Box::new(Logarithmic { max_temp, min_temp }),
+            Schedule::Adaptive { target_acceptance } => {
+                if !(0.0..=1.0).contains(target_acceptance) {
+                    return Err(anyhow::anyhow!(
+                        "Target acceptance must be between 0 and 1 but was {}",
+                        target_acceptance
+                    ));
+                }
+                Box::new(Adaptive {
+                    max_temp,
+                    min_temp,
+                    target_acceptance: *target_acceptance,
+                    temp: max_temp,
+                    accepted: 0,
+                    proposed: 0,
+                })
+            }
+            Schedule::Reheating { cycles, schedule } => {
+                if *cycles == 0 {
+                    return Err(anyhow::anyhow!("Reheating needs at least one cycle"));
+                }
+                Box::new(Reheating {
+                    cycles: *cycles,
+                    schedule: schedule.build(max_temp, min_temp)?,
+                })
+            }
+        };
+        Ok(schedule)
+    }
+}
+
+#[derive(Debug)]
+pub struct Linear {
+    pub max_temp: f64,
+    pub min_temp: f64,
+}
+
+impl CoolingSchedule for Linear {
+    fn temperature(&mut self, progress: f64) -> f64 {
+        let delta = self.max_temp - self.min_temp;
+        self.max_temp - delta * progress
+    }
+}
+
+#[derive(Debug)]
+pub struct Geometric {
+    pub max_temp: f64,
+    pub min_temp: f64,
+}
+
+impl CoolingSchedule for Geometric {
+    fn temperature(&mut self, progress: f64) -> f64 {
+        self.max_temp * (self.min_temp / self.max_temp).powf(progress)
+    }
+}
+
+#[derive(Debug)]
+pub struct Logarithmic {
+    pub max_temp: f64,
+    pub min_temp: f64,
 }
 
-fn linear_change_temp(max_temp: f64, min_temp: f64, steps: i32, step: i32) -> anyhow::Result<f64> {
-    if steps == 0 {
-        return Err(anyhow::anyhow!("steps must be greater than 0"));
+impl CoolingSchedule for Logarithmic {
+    /// `max_temp / (1 + c ln(1 + (e - 1) progress))`, with `c` chosen to end at `min_temp`.
+    fn temperature(&mut self, progress: f64) -> f64 {
+        let scale = self.max_temp / self.min_temp - 1.0;
+        let log = (1.0 + (std::f64::consts::E - 1.0) * progress).ln();
+        self.max_temp / (1.0 + scale * log)
+    }
+}
+
+#[derive(Debug)]
+pub struct Adaptive {
+    max_temp: f64,
+    min_temp: f64,
+    target_acceptance: f64,
+    temp: f64,
+    accepted: usize,
+    proposed: usize,
+}
+
+impl CoolingSchedule for Adaptive {
+    fn temperature(&mut self, progress: f64) -> f64 {
+        if self.proposed >= ADAPTIVE_WINDOW {
+            let target = self.target_acceptance * (1.0 - progress);
+            let acceptance = self.accepted as f64 / self.proposed as f64;
+            if acceptance > target {
+                self.temp *= ADAPTIVE_FACTOR;
+            } else {
+                self.temp /= ADAPTIVE_FACTOR;
+            }
+            self.temp = self.temp.clamp(self.min_temp, self.max_temp);
+            self.accepted = 0;
+            self.proposed = 0;
+        }
+        self.temp
+    }
+
+    fn record(&mut self, accepted: bool) {
+        self.proposed += 1;
+        if accepted {
+            self.accepted += 1;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Reheating {
+    cycles: u32,
+    schedule: Box<dyn CoolingSchedule>,
+}
+
+impl CoolingSchedule for Reheating {
+    fn temperature(&mut self, progress: f64) -> f64 {
+        self.schedule
+            .temperature((progress * self.cycles as f64).fract())
+    }
+
+    fn record(&mut self, accepted: bool) {
+        self.schedule.record(accepted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedules() {
+        let temperatures = |schedule: Schedule| {
+            let mut schedule = schedule.build(10.0, 0.1).unwrap();
+            [0.0, 0.5, 1.0].map(|progress| schedule.temperature(progress))
+        };
+        let close = |first: [f64; 3], second: [f64; 3]| {
+            first
+                .iter()
+                .zip(&second)
+                .all(|(first, second)| (first - second).abs() < 1e-9)
+        };
+        assert!(close(temperatures(Schedule::Linear), [10.0, 5.05, 0.1]));
+        assert!(close(temperatures(Schedule::Geometric), [10.0, 1.0, 0.1]));
+        let logarithmic = temperatures(Schedule::Logarithmic);
+        assert!(close(logarithmic, [10.0, logarithmic[1], 0.1]));
+        // cools faster than linear at first
+        assert!(logarithmic[1] < 5.05);
+        let reheating = Schedule::Reheating {
+            cycles: 2,
+            schedule: Box::new(Schedule::Linear),
+        };
+        assert_eq!(temperatures(reheating)[1], 10.0);
+
+        assert!(Schedule::Geometric.build(0.1, 10.0).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_schedule() {
+        let schedule = Schedule::Adaptive {
+            target_acceptance: 0.5,
+        };
+        let mut schedule = schedule.build(10.0, 0.1).unwrap();
+        // accepting every move cools the temperature down
+        for step in 0..=ADAPTIVE_WINDOW {
+            schedule.temperature(step as f64 / 100.0);
+            schedule.record(true);
+        }
+        assert_eq!(schedule.temperature(0.21), 10.0 * ADAPTIVE_FACTOR);
     }
-    let delta = max_temp - min_temp;
-    Ok(max_temp - (delta * step as f64) / steps as f64)
 }
@@ -1,38 +1,197 @@
-use rand::seq::SliceRandom;
-
-pub type StudentId = usize;
-pub type Students = Vec<usize>;
-#[derive(Debug, Clone)]
-pub struct RelationshipPair {
-    first_student_id: StudentId,
-    second_student_id: StudentId,
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::{collections::HashMap, time::Instant};
+
+use crate::solver::{
+    diagnosis, finish,
+    problem::{even_group_sizes, Problem, SizeBounds},
+    units::Units,
+    Exhausted, GroupId, RelationshipKind, RelationshipPair, Report, RunLimit, Solution, Solver,
+    StudentId,
+};
+
+/// Finds a grouping that keeps every relationship pair by trying each assignment in turn. Only
+/// the pairs, the pins and the group sizes are considered, and soft pairs are dropped if no
+/// grouping keeps them all. A step limit caps the number of placements tried in each attempt,
+/// which grows very quickly with the size of the class, so this suits small classes. Problems
+/// with other hard constraints are turned away.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Backtracking;
+
+impl Backtracking {
+    /// Fails if the problem has hard constraints other than pairs, pins and group sizes, which
+    /// backtracking would not keep.
+    pub fn check_supported(problem: &Problem) -> anyhow::Result<()> {
+        let unsupported = [
+            (
+                problem.spread_sets.iter().any(|spread_set| spread_set.hard),
+                "spread sets",
+            ),
+            (
+                problem.coverage.iter().any(|coverage| coverage.hard),
+                "coverage",
+            ),
+            (
+                problem
+                    .preferences
+                    .guarantee
+                    .as_ref()
+                    .is_some_and(|guarantee| guarantee.hard),
+                "guarantees",
+            ),
+        ];
+        let unsupported: Vec<&str> = unsupported
+            .into_iter()
+            .filter_map(|(present, kind)| present.then_some(kind))
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Backtracking cannot keep hard {}; choose another algorithm",
+                unsupported.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Solver for Backtracking {
+    fn solve(
+        &self,
+        problem: &Problem,
+        limit: RunLimit,
+        rng: &mut dyn RngCore,
+    ) -> anyhow::Result<Report> {
+        Self::check_supported(problem)?;
+        diagnosis::diagnose(problem)?;
+        let start = Instant::now();
+        let deadline = limit.time.map(|time| start + time);
+        let mut students = problem.students.clone();
+        students.shuffle(rng);
+        let hard: Vec<RelationshipPair> = problem
+            .relationship_pairs
+            .iter()
+            .filter(|pair| pair.is_hard())
+            .cloned()
+            .collect();
+        let mut attempts = vec![&problem.relationship_pairs];
+        if hard.len() < problem.relationship_pairs.len() {
+            attempts.push(&hard);
+        }
+        let num_attempts = attempts.len();
+        for (attempt, pairs) in attempts.into_iter().enumerate() {
+            // every attempt gets the full step limit and its share of the time left, so that
+            // dropping the soft pairs is still tried when keeping them used up the first attempt
+            let attempts_left = (num_attempts - attempt) as u32;
+            let mut budget = Budget::new(RunLimit {
+                steps: limit.steps,
+                time: deadline.map(|deadline| {
+                    deadline.saturating_duration_since(Instant::now()) / attempts_left
+                }),
+            });
+            let mut groups = vec![vec![]; problem.num_groups];
+            // soft pairs may contradict each other, but diagnosis has ruled that out for the
+            // hard ones
+            let Ok(found) = assign(
+                &students,
+                pairs,
+                &problem.allowed_groups,
+                &mut groups,
+                &problem.group_sizes,
+                &mut budget,
+            ) else {
+                continue;
+            };
+            if found {
+                let solution: Solution = groups
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(group, students)| {
+                        students
+                            .into_iter()
+                            .map(move |student| (student, GroupId::from(group)))
+                    })
+                    .collect();
+                return finish(problem, &solution);
+            }
+        }
+        // out of steps rather than proof that no grouping exists, so the other runs still get
+        // their turn
+        Err(Exhausted.into())
+    }
+}
+
+/// How many more placements the search may try, and until when.
+struct Budget {
+    steps: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    fn new(limit: RunLimit) -> Self {
+        Budget {
+            steps: limit.steps.map(|steps| steps.max(0) as usize),
+            deadline: limit.time.map(|time| Instant::now() + time),
+        }
+    }
+
+    fn unlimited() -> Self {
+        Budget {
+            steps: None,
+            deadline: None,
+        }
+    }
+
+    /// Takes a step, returning false once the budget is spent.
+    fn spend(&mut self) -> bool {
+        if let Some(steps) = &mut self.steps {
+            if *steps == 0 {
+                return false;
+            }
+            *steps -= 1;
+        }
+        self.deadline
+            .is_none_or(|deadline| Instant::now() < deadline)
+    }
 }
 
+/// Returns true if the unit fits into the group, is allowed there by the pins, and does not share
+/// it with a student it must be kept apart from.
 fn is_valid_assignment(
-    student: StudentId,
+    unit: &[StudentId],
     group: usize,
     constraints: &[RelationshipPair],
+    allowed_groups: &HashMap<StudentId, Vec<GroupId>>,
     groups: &[Vec<StudentId>],
-    students_per_group: usize,
+    max_students_in_group: usize,
 ) -> bool {
-    // Check if adding the student violates the even distribution
-    if groups[group].len() >= students_per_group {
+    if groups[group].len() + unit.len() > max_students_in_group {
         return false;
     }
-    let passes_criteria = constraints.iter().all(|constraint| {
-        let first_student = constraint.first_student_id;
-        let second_student = constraint.second_student_id;
-        if student == first_student && groups[group].contains(&second_student) {
-            return false;
-        }
-        if student == second_student && groups[group].contains(&first_student) {
-            return false;
-        }
-        true
+    let pinned_elsewhere = unit.iter().any(|student| {
+        allowed_groups
+            .get(student)
+            .is_some_and(|allowed| !allowed.contains(&GroupId::from(group)))
     });
+    if pinned_elsewhere {
+        return false;
+    }
+    let passes_criteria = constraints
+        .iter()
+        .filter(|constraint| constraint.kind() == RelationshipKind::Apart)
+        .all(|constraint| {
+            let (first_student, second_student) = constraint.students();
+            if unit.contains(first_student) && groups[group].contains(second_student) {
+                return false;
+            }
+            if unit.contains(second_student) && groups[group].contains(first_student) {
+                return false;
+            }
+            true
+        });
     passes_criteria
 }
 
+/// Assigns students to groups that are as equal in size as possible. Together pairs are not
+/// considered.
 pub fn assign_students(
     students: &[StudentId],
     constraints: &[RelationshipPair],
@@ -40,78 +199,271 @@ pub fn assign_students(
     n_groups: usize,
     index: usize,
 ) -> bool {
-    if index == students.len() {
+    let group_sizes = even_group_sizes(students.len(), n_groups);
+    assign_students_with_sizes(students, constraints, groups, &group_sizes, index)
+}
+
+/// Assigns students to groups whose sizes lie within the given bounds, one bound per group.
+pub fn assign_students_with_sizes(
+    students: &[StudentId],
+    constraints: &[RelationshipPair],
+    groups: &mut Vec<Vec<StudentId>>,
+    group_sizes: &[SizeBounds],
+    index: usize,
+) -> bool {
+    let units: Vec<Vec<StudentId>> = students[index..]
+        .iter()
+        .map(|student| vec![student.clone()])
+        .collect();
+    assign_units(
+        &units,
+        constraints,
+        &HashMap::new(),
+        groups,
+        group_sizes,
+        0,
+        &mut Budget::unlimited(),
+    )
+}
+
+/// Assigns the students while keeping each together pair in the same group and each apart pair
+/// in different groups. Chains of together pairs are contracted into units, the units are
+/// assigned as a whole and then expanded back into their students. Returns an error if two
+/// students must be both together and apart.
+pub fn assign_students_together(
+    students: &[StudentId],
+    pairs: &[RelationshipPair],
+    groups: &mut Vec<Vec<StudentId>>,
+    group_sizes: &[SizeBounds],
+) -> anyhow::Result<bool> {
+    assign(
+        students,
+        pairs,
+        &HashMap::new(),
+        groups,
+        group_sizes,
+        &mut Budget::unlimited(),
+    )
+}
+
+fn assign(
+    students: &[StudentId],
+    pairs: &[RelationshipPair],
+    allowed_groups: &HashMap<StudentId, Vec<GroupId>>,
+    groups: &mut Vec<Vec<StudentId>>,
+    group_sizes: &[SizeBounds],
+    budget: &mut Budget,
+) -> anyhow::Result<bool> {
+    let of_kind = |kind| {
+        pairs
+            .iter()
+            .filter(move |pair| pair.kind() == kind)
+            .map(RelationshipPair::students)
+    };
+    let units = Units::new(students, of_kind(RelationshipKind::Together));
+    if let Err((first, second)) = units.separations(of_kind(RelationshipKind::Apart)) {
+        return Err(anyhow::anyhow!(
+            "Students {} and {} must be both together and apart",
+            first,
+            second
+        ));
+    }
+    let mut units: Vec<Vec<StudentId>> = (0..units.len())
+        .map(|unit| units.members(unit).to_vec())
+        .collect();
+    // the largest units are the hardest to fit, so place them first
+    units.sort_by_key(|unit| std::cmp::Reverse(unit.len()));
+    Ok(assign_units(
+        &units,
+        pairs,
+        allowed_groups,
+        groups,
+        group_sizes,
+        0,
+        budget,
+    ))
+}
+
+fn assign_units(
+    units: &[Vec<StudentId>],
+    constraints: &[RelationshipPair],
+    allowed_groups: &HashMap<StudentId, Vec<GroupId>>,
+    groups: &mut Vec<Vec<StudentId>>,
+    group_sizes: &[SizeBounds],
+    index: usize,
+    budget: &mut Budget,
+) -> bool {
+    let students_left: usize = units[index..].iter().map(Vec::len).sum();
+    let students_needed: usize = groups
+        .iter()
+        .zip(group_sizes)
+        .map(|(group, bounds)| bounds.min.saturating_sub(group.len()))
+        .sum();
+    if students_needed > students_left {
+        return false;
+    }
+    if index == units.len() {
         return true; // All students have been successfully assigned
     }
-    let total_students = students.len();
-    let base_students_per_group = total_students / n_groups;
-    let mut extra_students_needed = total_students % n_groups;
-
-    for group in 0..n_groups {
-        // Determine the allowed number of students in this group
-        let allowed_students_in_group = if extra_students_needed > 0 {
-            extra_students_needed -= 1; // Account for an extra student in this group
-            base_students_per_group + 1
-        } else {
-            base_students_per_group
-        };
-        if is_valid_assignment(
-            students[index],
-            group,
-            constraints,
-            groups,
-            allowed_students_in_group,
-        ) {
-            groups[group].push(students[index]); // Tentatively assign student to this group
-            if assign_students(students, constraints, groups, n_groups, index + 1) {
+
+    let unit = &units[index];
+    for (group, bounds) in group_sizes.iter().enumerate() {
+        if !budget.spend() {
+            return false;
+        }
+        if is_valid_assignment(unit, group, constraints, allowed_groups, groups, bounds.max) {
+            groups[group].extend(unit.iter().cloned()); // Tentatively assign the unit to this group
+            if assign_units(
+                units,
+                constraints,
+                allowed_groups,
+                groups,
+                group_sizes,
+                index + 1,
+                budget,
+            ) {
                 return true; // Found a valid assignment for all students
             }
-            groups[group].pop(); // Backtrack
+            let size = groups[group].len() - unit.len();
+            groups[group].truncate(size); // Backtrack
         }
     }
 
-    false // No valid assignment was found for this student
+    false // No valid assignment was found for this unit
 }
 
+/// Makes random pairs of students who must be kept apart.
 pub fn generate_random_constraints(
     students: &[StudentId],
     n_constraints: usize,
+    rng: &mut impl Rng,
 ) -> Vec<RelationshipPair> {
-    let mut rng = rand::thread_rng();
     (0..n_constraints)
         .map(|_| {
             // Generate two different student ids
-            let first_student_id = *students.choose(&mut rng).unwrap();
-            let mut second_student_id = *students.choose(&mut rng).unwrap();
+            let first_student_id = students.choose(rng).unwrap().clone();
+            let mut second_student_id = students.choose(rng).unwrap().clone();
             while first_student_id == second_student_id {
-                second_student_id = *students.choose(&mut rng).unwrap();
+                second_student_id = students.choose(rng).unwrap().clone();
             }
 
-            RelationshipPair {
+            RelationshipPair::new(
                 first_student_id,
                 second_student_id,
-            }
+                RelationshipKind::Apart,
+                1.0,
+                true,
+            )
         })
         .collect()
 }
 
 pub fn check_for_constraint_violations(
     groups: &[Vec<StudentId>],
-    constraints: &Vec<RelationshipPair>,
+    constraints: &[RelationshipPair],
 ) -> bool {
     let mut violated = false;
     for constraint in constraints {
-        let first_student = constraint.first_student_id;
-        let second_student = constraint.second_student_id;
-        for group in groups {
-            if group.contains(&first_student) && group.contains(&second_student) {
-                violated = true;
-                break;
-            }
+        let (first_student, second_student) = constraint.students();
+        let together = groups
+            .iter()
+            .any(|group| group.contains(first_student) && group.contains(second_student));
+        if together != (constraint.kind() == RelationshipKind::Together) {
+            violated = true;
+            break;
         }
     }
     violated
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::constraints::SpreadSet;
+
+    use super::*;
+
+    #[test]
+    fn test_assign_students_together_contradiction() {
+        let students: Vec<StudentId> = (0..8).map(StudentId::from).collect();
+        let pair = |first: usize, second: usize, kind| {
+            RelationshipPair::new(first.into(), second.into(), kind, 1.0, true)
+        };
+        let mut pairs = vec![
+            pair(0, 1, RelationshipKind::Together),
+            pair(1, 2, RelationshipKind::Together),
+            pair(3, 4, RelationshipKind::Apart),
+        ];
+        let group_sizes = even_group_sizes(8, 2);
+        let mut groups = vec![vec![]; 2];
+        assert!(assign_students_together(&students, &pairs, &mut groups, &group_sizes).unwrap());
+        assert!(!check_for_constraint_violations(&groups, &pairs));
+
+        pairs.push(pair(2, 0, RelationshipKind::Apart));
+        let mut groups = vec![vec![]; 2];
+        let error =
+            assign_students_together(&students, &pairs, &mut groups, &group_sizes).unwrap_err();
+        assert!(error.to_string().contains("both together and apart"));
+    }
+
+    #[test]
+    fn test_backtracking_drops_soft_pairs_after_running_out_of_steps() {
+        let students: Vec<StudentId> = (0..12).map(StudentId::from).collect();
+        // four students who should all be apart, with only three groups
+        let mut pairs = vec![];
+        for first in 0..4 {
+            for second in first + 1..4 {
+                pairs.push(RelationshipPair::new(
+                    first.into(),
+                    second.into(),
+                    RelationshipKind::Apart,
+                    1.0,
+                    false,
+                ));
+            }
+        }
+        let problem = Problem::new(students, 3, pairs);
+        let mut rng = rand::rngs::mock::StepRng::new(0, 1);
+        let report = Backtracking
+            .solve(&problem, RunLimit::steps(100), &mut rng)
+            .unwrap();
+        assert_eq!(report.groups.iter().map(Vec::len).sum::<usize>(), 12);
+    }
+
+    #[test]
+    fn test_backtracking_out_of_steps() {
+        let students: Vec<StudentId> = (0..12).map(StudentId::from).collect();
+        let pairs = vec![RelationshipPair::new(
+            0.into(),
+            1.into(),
+            RelationshipKind::Apart,
+            1.0,
+            true,
+        )];
+        let problem = Problem::new(students, 3, pairs);
+        let mut rng = rand::rngs::mock::StepRng::new(0, 1);
+        let result = Backtracking.solve(&problem, RunLimit::steps(1), &mut rng);
+        assert!(result.is_err_and(|error| error.is::<Exhausted>()));
+    }
+
+    #[test]
+    fn test_backtracking_rejects_hard_spread_sets() {
+        let students: Vec<StudentId> = (0..12).map(StudentId::from).collect();
+        let spread_set = SpreadSet {
+            students: vec![0.into(), 1.into()],
+            max_per_group: 1,
+            weight: 1.0,
+            hard: true,
+        };
+        let problem = Problem::new(students, 3, vec![])
+            .with_spread_sets(vec![spread_set])
+            .unwrap();
+        let mut rng = rand::rngs::mock::StepRng::new(0, 1);
+        assert!(Backtracking
+            .solve(&problem, RunLimit::steps(1000), &mut rng)
+            .is_err());
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
@@ -0,0 +1,272 @@
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::time::Instant;
+use tracing::debug;
+
+use crate::solver::{
+    diagnosis,
+    evaluation::{Move, State},
+    finish, good_enough_score,
+    group_move::{propose_move, random_solution},
+    Problem, Report, RunLimit, Solver,
+};
+
+/// How many moves of local search each new grouping gets, for each unit of the class and at
+/// least.
+const LOCAL_SEARCH_STEPS_PER_UNIT: usize = 1;
+const MIN_LOCAL_SEARCH_STEPS: usize = 100;
+/// How many groupings compete to become a parent.
+const TOURNAMENT_SIZE: usize = 2;
+
+/// Runs [`genetic_search`] as a [`Solver`].
+#[derive(Debug, Clone, Copy)]
+pub struct GeneticSearch {
+    pub population: usize,
+}
+
+impl Solver for GeneticSearch {
+    fn solve(
+        &self,
+        problem: &Problem,
+        limit: RunLimit,
+        mut rng: &mut dyn RngCore,
+    ) -> anyhow::Result<Report> {
+        genetic_search(problem, self.population, limit, &mut rng)
+    }
+
+    fn moves_per_step(&self, problem: &Problem) -> u64 {
+        // the local search of the child, and the crossover trying the groups for left over units
+        (local_search_steps(problem) + problem.units.len() * problem.num_groups) as u64
+    }
+}
+
+/// Random groupings improved by local search, as many as asked for or as the time limit allows,
+/// but at least one.
+fn initial_population<'a>(
+    problem: &'a Problem,
+    population_size: usize,
+    limit: RunLimit,
+    start: Instant,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Vec<State<'a>>> {
+    let mut population = vec![];
+    while population.len() < population_size
+        && (population.is_empty() || limit.progress(0, start).is_some())
+    {
+        let mut state = State::new(problem, &random_solution(problem, rng)?)?;
+        local_search(&mut state, problem, rng)?;
+        population.push(state);
+    }
+    Ok(population)
+}
+
+/// Searches for a good grouping with a population of `population_size` groupings. Each step
+/// crosses two parents chosen by tournament into a child, improves the child by local search, and
+/// lets it replace the worst grouping if it is better and not already in the population. A step
+/// limit counts children. Under a time limit, the population stops growing once the time is up,
+/// even if it is not yet full.
+#[tracing::instrument(skip(rng))]
+pub fn genetic_search(
+    problem: &Problem,
+    population_size: usize,
+    limit: RunLimit,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Report> {
+    diagnosis::diagnose(problem)?;
+    let start = Instant::now();
+    let lower_bound = good_enough_score(problem);
+    let mut population = initial_population(problem, population_size, limit, start, rng)?;
+
+    let mut step = 0;
+    while limit.progress(step, start).is_some() {
+        if best(&population).score() <= lower_bound {
+            debug!("Reached the lower bound after {} steps", step);
+            break;
+        }
+        let first = tournament(&population, rng);
+        let second = tournament(&population, rng);
+        let mut child = crossover(first, second, problem, rng);
+        local_search(&mut child, problem, rng)?;
+
+        let (worst, worst_score) = population
+            .iter()
+            .map(State::score)
+            .enumerate()
+            .max_by(|(_, first), (_, second)| first.total_cmp(second))
+            .expect("the population is never empty");
+        if child.score() < worst_score
+            && population
+                .iter()
+                .all(|member| member.assignment() != child.assignment())
+        {
+            population[worst] = child;
+        }
+        step += 1;
+    }
+    let best = best(&population);
+    finish(problem, &best.solution_of(best.assignment()))
+}
+
+/// The grouping with the lowest objective.
+fn best<'s, 'a>(population: &'s [State<'a>]) -> &'s State<'a> {
+    population
+        .iter()
+        .min_by(|first, second| first.score().total_cmp(&second.score()))
+        .expect("the population is never empty")
+}
+
+/// The best of a few groupings drawn at random.
+fn tournament<'s, 'a>(population: &'s [State<'a>], rng: &mut impl Rng) -> &'s State<'a> {
+    population
+        .choose_multiple(rng, TOURNAMENT_SIZE)
+        .min_by(|first, second| first.score().total_cmp(&second.score()))
+        .expect("the population is never empty")
+}
+
+/// Crosses two groupings group by group rather than student by student, so that groups that work
+/// well are passed on whole. The child keeps a random half of the first parent's groups as they
+/// are. Every other unit goes to its group in the second parent, unless that group was kept from
+/// the first parent; the units left over then go to whichever group with room lowers the
+/// objective most, or stay where they are.
+fn crossover<'a>(
+    first: &State<'a>,
+    second: &State<'a>,
+    problem: &Problem,
+    rng: &mut impl Rng,
+) -> State<'a> {
+    let num_groups = problem.num_groups;
+    let kept: Vec<bool> = (0..num_groups).map(|_| rng.gen_bool(0.5)).collect();
+    let mut child = first.clone();
+    let mut left_over = vec![];
+    let mut changes = vec![];
+    for unit in 0..problem.units.len() {
+        if kept[first.unit_group(unit)] {
+            continue;
+        }
+        let group = second.unit_group(unit);
+        if kept[group] {
+            left_over.push(unit);
+        } else {
+            changes.push((unit, group));
+        }
+    }
+    child.apply(&Move { changes });
+
+    left_over.shuffle(rng);
+    let bounds = &problem.group_sizes;
+    for unit in left_over {
+        let size = child.unit_size(unit);
+        let current = child.unit_group(unit);
+        let candidates: Vec<usize> = (0..num_groups)
+            .filter(|&group| {
+                !kept[group]
+                    && group != current
+                    && child.allows(unit, group)
+                    && child.group_sizes()[group] + size <= bounds[group].max
+            })
+            .collect();
+        let best_move = candidates
+            .into_iter()
+            .map(|group| {
+                let (inverse, delta) = child.apply(&Move::unit(unit, group));
+                child.undo(&inverse, delta);
+                (group, delta.score)
+            })
+            .min_by(|(_, first), (_, second)| first.total_cmp(second));
+        if let Some((group, delta)) = best_move {
+            if delta < 0.0 {
+                child.apply(&Move::unit(unit, group));
+            }
+        }
+    }
+    child
+}
+
+fn local_search_steps(problem: &Problem) -> usize {
+    (problem.units.len() * LOCAL_SEARCH_STEPS_PER_UNIT).max(MIN_LOCAL_SEARCH_STEPS)
+}
+
+/// Improves a grouping with random moves, keeping those that neither make it worse nor break
+/// more hard constraints.
+fn local_search(state: &mut State, problem: &Problem, rng: &mut impl Rng) -> anyhow::Result<()> {
+    for _ in 0..local_search_steps(problem) {
+        let change = propose_move(state, problem, rng)?;
+        let (inverse, delta) = state.apply(&change);
+        if delta.score > 0.0 || delta.hard_violations > 0 {
+            state.undo(&inverse, delta);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Duration;
+
+    use crate::solver::{
+        group_move::within_bounds,
+        preferences::{Guarantee, Preference, Preferences},
+        problem::even_group_sizes,
+        RelationshipKind, RelationshipPair, StudentId,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_genetic_search() {
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
+        let pairs = (0..21)
+            .map(|i| {
+                RelationshipPair::new(
+                    i.into(),
+                    ((i + 1) % 21).into(),
+                    RelationshipKind::Apart,
+                    1.0,
+                    i % 2 == 0,
+                )
+            })
+            .collect();
+        let preferences = Preferences {
+            students: (0..21)
+                .map(|i| Preference {
+                    student_id: i.into(),
+                    wishes: vec![((i + 4) % 21).into(), ((i + 9) % 21).into()],
+                })
+                .collect(),
+            guarantee: Some(Guarantee {
+                weight: 1.0,
+                hard: true,
+            }),
+            ..Default::default()
+        };
+        let problem = Problem::new(students, 4, pairs)
+            .with_preferences(preferences)
+            .unwrap();
+        let solve = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            genetic_search(&problem, 10, RunLimit::steps(100), &mut rng).unwrap()
+        };
+        let result = solve(4);
+        assert!(result
+            .violations
+            .iter()
+            .all(|violation| !violation.is_hard()));
+        let group_sizes: Vec<usize> = result.groups.iter().map(Vec::len).collect();
+        assert!(within_bounds(&group_sizes, &even_group_sizes(21, 4)));
+        assert_eq!(solve(4).groups, result.groups);
+    }
+
+    #[test]
+    fn test_initial_population_keeps_to_time_limit() {
+        let students: Vec<StudentId> = (0..30).map(StudentId::from).collect();
+        let problem = Problem::new(students, 3, vec![]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let limit = RunLimit::time(Duration::from_secs(1));
+        let population = initial_population(&problem, 20, limit, Instant::now(), &mut rng);
+        assert_eq!(population.unwrap().len(), 20);
+        // once the time is up, only the one grouping needed to go on is made
+        let late = Instant::now() - Duration::from_secs(2);
+        let population = initial_population(&problem, 20, limit, late, &mut rng);
+        assert_eq!(population.unwrap().len(), 1);
+    }
+}
@@ -1,2 +1,5 @@
 pub mod annealing;
 pub mod backtracking;
+pub mod genetic;
+pub mod solver;
+pub mod tabu;
@@ -4,10 +4,13 @@ use axum::{
     routing::get,
     Json, Router,
 };
-use grouping::annealing::{
-    http::{CreateGroupsRequest, CreateGroupsResponse},
-    simulated_annealing,
+use grouping::solver::{
+    diagnosis::Conflict,
+    http::{CreateGroupsRequest, CreateGroupsResponse, InvalidRequest},
+    parameters::InvalidParameters,
+    solve, Exhausted, Infeasible,
 };
+use rand::{rngs::StdRng, SeedableRng};
 use tower_http::trace::TraceLayer;
 
 #[tokio::main]
@@ -28,13 +31,26 @@ async fn main() -> anyhow::Result<()> {
 async fn create_groups(
     create_groups_request: Json<CreateGroupsRequest>,
 ) -> Result<Json<CreateGroupsResponse>, AppError> {
-    let (num_groups, students, relationship_pairs) = create_groups_request.0.into_parts();
-    let annealing_result =
-        simulated_annealing(students, num_groups, &relationship_pairs, 10.0, 0.1, 1000)?;
+    let seed = create_groups_request.seed.unwrap_or_else(rand::random);
+    let solver = create_groups_request.solver.clone();
+    let (problem, sizing) = create_groups_request.0.into_problem()?;
+    let parameters = solver.resolve(&problem)?;
+    // solving may take tens of seconds on threads of its own, so keep it off the async workers
+    let (parameters, (report, statistics)) = tokio::task::spawn_blocking(move || {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let solved = solve(&problem, &parameters, &mut rng)?;
+        anyhow::Ok((parameters, solved))
+    })
+    .await??;
     let response = CreateGroupsResponse {
-        groups: annealing_result.groups,
-        violations: annealing_result.violations,
-        objective: annealing_result.objective,
+        groups: report.groups,
+        violations: report.violations,
+        objective: report.objective,
+        sizing,
+        satisfaction: report.satisfaction,
+        seed,
+        parameters,
+        statistics,
     };
     Ok(Json(response))
 }
@@ -44,6 +60,20 @@ struct AppError(anyhow::Error);
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let Some(infeasible) = self.0.downcast_ref::<Infeasible>() {
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(infeasible)).into_response();
+        }
+        if self.0.downcast_ref::<Exhausted>().is_some() {
+            return (StatusCode::UNPROCESSABLE_ENTITY, self.0.to_string()).into_response();
+        }
+        if let Some(conflict) = self.0.downcast_ref::<Conflict>() {
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(conflict)).into_response();
+        }
+        if self.0.downcast_ref::<InvalidParameters>().is_some()
+            || self.0.downcast_ref::<InvalidRequest>().is_some()
+        {
+            return (StatusCode::BAD_REQUEST, format!("{:#}", self.0)).into_response();
+        }
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Something went wrong: {}", self.0),
@@ -0,0 +1,66 @@
+use super::{problem::Problem, RelationshipKind, Solution, Violation};
+
+/// Returns the violated constraints, most severe first.
+pub(crate) fn get_violations(solution: &Solution, problem: &Problem) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = problem
+        .relationship_pairs
+        .iter()
+        .filter(|restriction| {
+            restriction.is_violated(
+                &solution[&restriction.first_student_id],
+                &solution[&restriction.second_student_id],
+            )
+        })
+        .map(|restriction| Violation::Pair {
+            relationship_pair: restriction.clone(),
+            group: solution[&restriction.first_student_id].clone(),
+            weight: restriction.weight,
+            second_group: match restriction.kind {
+                RelationshipKind::Apart => None,
+                RelationshipKind::Together => {
+                    Some(solution[&restriction.second_student_id].clone())
+                }
+            },
+        })
+        .collect();
+    for spread_set in &problem.spread_sets {
+        for (group, students) in spread_set.crowded_groups(solution) {
+            violations.push(Violation::SpreadSet {
+                spread_set: spread_set.clone(),
+                group,
+                students,
+                weight: spread_set.weight,
+            });
+        }
+    }
+    for coverage in &problem.coverage {
+        let tagged = problem.tagged_students(&coverage.tag);
+        for (group, found) in coverage
+            .counts(solution, tagged, problem.num_groups)
+            .into_iter()
+            .enumerate()
+        {
+            if found < coverage.min_per_group {
+                violations.push(Violation::Coverage {
+                    coverage: coverage.clone(),
+                    group: group.into(),
+                    found,
+                    weight: coverage.weight,
+                });
+            }
+        }
+    }
+    if let Some(guarantee) = &problem.preferences.guarantee {
+        for (preference, group) in problem.preferences.isolated_students(solution) {
+            violations.push(Violation::Isolated {
+                student_id: preference.student_id.clone(),
+                group: group.clone(),
+                wishes: preference.wishes.clone(),
+                guarantee: guarantee.clone(),
+                weight: guarantee.weight,
+            });
+        }
+    }
+    violations.sort_by(|a, b| b.weight().total_cmp(&a.weight()));
+    violations
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{default_weight, problem::SizeBounds, Solution, StudentId};
+
+/// The value of a student attribute: numbers such as a reading level, or categories such as
+/// gender.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    Numeric(f64),
+    Categorical(String),
+}
+
+pub type Attributes = HashMap<String, AttributeValue>;
+
+/// Whether students should be mixed or grouped by an attribute.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeMode {
+    /// Every group should look like the class as a whole.
+    #[default]
+    Balance,
+    /// Students with similar values should share a group. Only numeric attributes can be grouped
+    /// this way.
+    Homogeneous,
+}
+
+/// Asks for an attribute to be spread evenly across the groups, or for students with similar
+/// values to be grouped together.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AttributeObjective {
+    pub attribute: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub mode: AttributeMode,
+}
+
+/// The values of one attribute, checked for a consistent type and indexed for scoring.
+#[derive(Debug, Clone)]
+pub enum AttributeValues {
+    /// Category index of each student that has the attribute, ordered by student.
+    Categorical {
+        categories: Vec<(StudentId, usize)>,
+        num_categories: usize,
+        /// Students in each category.
+        totals: Vec<usize>,
+    },
+    /// Value of each student that has the attribute, ordered by student.
+    Numeric {
+        values: Vec<(StudentId, f64)>,
+        mean: f64,
+        variance: f64,
+    },
+}
+
+/// The value of one attribute for a single student, with categories as indices.
+#[derive(Debug, Clone, Copy)]
+pub enum StudentValue {
+    Category(usize),
+    Number(f64),
+}
+
+/// Running totals of one attribute over the students in a group.
+#[derive(Debug, Clone)]
+pub enum GroupStats {
+    /// Students in each category.
+    Categorical(Vec<usize>),
+    Numeric {
+        sum: f64,
+        sum_of_squares: f64,
+        count: usize,
+    },
+}
+
+impl GroupStats {
+    pub fn add(&mut self, value: StudentValue) {
+        match (self, value) {
+            (GroupStats::Categorical(counts), StudentValue::Category(category)) => {
+                counts[category] += 1;
+            }
+            (
+                GroupStats::Numeric {
+                    sum,
+                    sum_of_squares,
+                    count,
+                },
+                StudentValue::Number(value),
+            ) => {
+                *sum += value;
+                *sum_of_squares += value * value;
+                *count += 1;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn remove(&mut self, value: StudentValue) {
+        match (self, value) {
+            (GroupStats::Categorical(counts), StudentValue::Category(category)) => {
+                counts[category] -= 1;
+            }
+            (
+                GroupStats::Numeric {
+                    sum,
+                    sum_of_squares,
+                    count,
+                },
+                StudentValue::Number(value),
+            ) => {
+                *sum -= value;
+                *sum_of_squares -= value * value;
+                *count -= 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// An attribute objective resolved against the students of a problem.
+#[derive(Debug, Clone)]
+pub struct AttributeTerm {
+    pub attribute: String,
+    pub weight: f64,
+    pub mode: AttributeMode,
+    pub values: AttributeValues,
+}
+
+impl AttributeTerm {
+    pub fn new(
+        objective: &AttributeObjective,
+        attributes: &HashMap<StudentId, Attributes>,
+    ) -> anyhow::Result<Self> {
+        if !objective.weight.is_finite() || objective.weight < 0.0 {
+            return Err(anyhow::anyhow!(
+                "Invalid weight {} for attribute {}",
+                objective.weight,
+                objective.attribute
+            ));
+        }
+        let mut present: Vec<(&StudentId, &AttributeValue)> = attributes
+            .iter()
+            .filter_map(|(student, attributes)| {
+                attributes
+                    .get(&objective.attribute)
+                    .map(|value| (student, value))
+            })
+            .collect();
+        present.sort_by_key(|(student, _)| ***student);
+        let values = match present.first() {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "No student has the attribute {}",
+                    objective.attribute
+                ))
+            }
+            Some((_, AttributeValue::Categorical(_))) => {
+                let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+                let mut categories = vec![];
+                for (student, value) in &present {
+                    let AttributeValue::Categorical(name) = value else {
+                        return Err(mixed_types(&objective.attribute));
+                    };
+                    let num_names = names.len();
+                    let category = *names.entry(name).or_insert(num_names);
+                    categories.push(((*student).clone(), category));
+                }
+                let mut totals = vec![0; names.len()];
+                for (_, category) in &categories {
+                    totals[*category] += 1;
+                }
+                AttributeValues::Categorical {
+                    categories,
+                    num_categories: names.len(),
+                    totals,
+                }
+            }
+            Some((_, AttributeValue::Numeric(_))) => {
+                let mut values: Vec<(StudentId, f64)> = vec![];
+                for (student, value) in &present {
+                    let AttributeValue::Numeric(number) = value else {
+                        return Err(mixed_types(&objective.attribute));
+                    };
+                    if !number.is_finite() {
+                        return Err(anyhow::anyhow!(
+                            "Invalid value {} for attribute {}",
+                            number,
+                            objective.attribute
+                        ));
+                    }
+                    values.push(((*student).clone(), *number));
+                }
+                let mean = values.iter().map(|(_, value)| value).sum::<f64>() / values.len() as f64;
+                let variance = values
+                    .iter()
+                    .map(|(_, value)| (value - mean).powi(2))
+                    .sum::<f64>()
+                    / values.len() as f64;
+                AttributeValues::Numeric {
+                    values,
+                    mean,
+                    variance,
+                }
+            }
+        };
+        if objective.mode == AttributeMode::Homogeneous
+            && matches!(values, AttributeValues::Categorical { .. })
+        {
+            return Err(anyhow::anyhow!(
+                "Attribute {} must be numeric to group homogeneously",
+                objective.attribute
+            ));
+        }
+        Ok(AttributeTerm {
+            attribute: objective.attribute.clone(),
+            weight: objective.weight,
+            mode: objective.mode,
+            values,
+        })
+    }
+
+    /// The value of each student that has the attribute, ordered by student.
+    pub fn student_values(&self) -> Vec<(&StudentId, StudentValue)> {
+        match &self.values {
+            AttributeValues::Categorical { categories, .. } => categories
+                .iter()
+                .map(|(student, category)| (student, StudentValue::Category(*category)))
+                .collect(),
+            AttributeValues::Numeric { values, .. } => values
+                .iter()
+                .map(|(student, value)| (student, StudentValue::Number(*value)))
+                .collect(),
+        }
+    }
+
+    pub fn empty_stats(&self) -> GroupStats {
+        match &self.values {
+            AttributeValues::Categorical { num_categories, .. } => {
+                GroupStats::Categorical(vec![0; *num_categories])
+            }
+            AttributeValues::Numeric { .. } => GroupStats::Numeric {
+                sum: 0.0,
+                sum_of_squares: 0.0,
+                count: 0,
+            },
+        }
+    }
+
+    pub fn penalty(&self, solution: &Solution, num_groups: usize) -> f64 {
+        let mut stats = vec![self.empty_stats(); num_groups];
+        for (student, value) in self.student_values() {
+            stats[*solution[student]].add(value);
+        }
+        // students without the attribute still take up room in their group
+        let mut group_sizes = vec![0usize; num_groups];
+        for group in solution.values() {
+            group_sizes[**group] += 1;
+        }
+        stats
+            .iter()
+            .zip(&group_sizes)
+            .map(|(stats, &group_size)| {
+                self.group_penalty(stats, group_size, solution.len(), num_groups)
+            })
+            .sum()
+    }
+
+    /// The share of the penalty due to one group, so the penalty can be updated group by group.
+    ///
+    /// Balancing penalises groups whose make-up differs from the class as a whole. For categories
+    /// this is the squared difference between the group's count and the count its size would give
+    /// at the class-wide proportion; for numbers it is the squared difference between group and
+    /// class means, relative to the class variance. Grouping homogeneously penalises the spread of
+    /// values within the group: the sum of squared differences from the group mean, relative to
+    /// the class variance.
+    pub fn group_penalty(
+        &self,
+        stats: &GroupStats,
+        group_size: usize,
+        num_students: usize,
+        num_groups: usize,
+    ) -> f64 {
+        let penalty = match (&self.values, stats) {
+            (AttributeValues::Categorical { totals, .. }, GroupStats::Categorical(counts)) => {
+                counts
+                    .iter()
+                    .zip(totals)
+                    .map(|(&count, &total)| {
+                        let expected = group_size as f64 * total as f64 / num_students as f64;
+                        (count as f64 - expected).powi(2)
+                    })
+                    .sum::<f64>()
+            }
+            (
+                AttributeValues::Numeric { mean, variance, .. },
+                &GroupStats::Numeric {
+                    sum,
+                    sum_of_squares,
+                    count,
+                },
+            ) => {
+                if *variance == 0.0 || count == 0 {
+                    return 0.0;
+                }
+                match self.mode {
+                    AttributeMode::Balance => (sum / count as f64 - mean).powi(2) / variance,
+                    AttributeMode::Homogeneous => {
+                        (sum_of_squares - sum * sum / count as f64) / variance
+                    }
+                }
+            }
+            _ => 0.0,
+        };
+        self.weight * penalty / num_groups as f64
+    }
+
+    /// A penalty no grouping can go below. When every group has an exact size, each group's
+    /// expected count of a category is known and the actual count can at best round it to a whole
+    /// number of students.
+    pub fn lower_bound(&self, group_sizes: &[SizeBounds], num_students: usize) -> f64 {
+        let AttributeValues::Categorical { totals, .. } = &self.values else {
+            return 0.0;
+        };
+        if self.mode != AttributeMode::Balance
+            || num_students == 0
+            || group_sizes.iter().any(|bounds| bounds.min != bounds.max)
+        {
+            return 0.0;
+        }
+        let penalty: f64 = group_sizes
+            .iter()
+            .map(|bounds| {
+                totals
+                    .iter()
+                    .map(|&total| {
+                        let expected = bounds.min as f64 * total as f64 / num_students as f64;
+                        (expected - expected.round()).powi(2)
+                    })
+                    .sum::<f64>()
+            })
+            .sum();
+        self.weight * penalty / group_sizes.len() as f64
+    }
+}
+
+fn mixed_types(attribute: &str) -> anyhow::Error {
+    anyhow::anyhow!("Attribute {} mixes numbers and categories", attribute)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::GroupId;
+
+    use super::*;
+
+    /// Two girls and two boys with reading levels 0 to 3, girls in group 0 and boys in group 1.
+    fn four_students() -> (HashMap<StudentId, Attributes>, Solution) {
+        let mut attributes: HashMap<StudentId, Attributes> = HashMap::new();
+        let mut solution = Solution::new();
+        for student in 0..4 {
+            let gender = if student < 2 { "f" } else { "m" };
+            attributes.insert(
+                StudentId(student),
+                HashMap::from([
+                    (
+                        "gender".to_string(),
+                        AttributeValue::Categorical(gender.to_string()),
+                    ),
+                    (
+                        "reading".to_string(),
+                        AttributeValue::Numeric(student as f64),
+                    ),
+                ]),
+            );
+            solution.insert(StudentId(student), GroupId(student / 2));
+        }
+        (attributes, solution)
+    }
+
+    #[test]
+    fn test_balance_penalty() {
+        let (attributes, mut solution) = four_students();
+        let objective = |attribute: &str| AttributeObjective {
+            attribute: attribute.to_string(),
+            weight: 1.0,
+            mode: AttributeMode::Balance,
+        };
+        let gender = AttributeTerm::new(&objective("gender"), &attributes).unwrap();
+        let reading = AttributeTerm::new(&objective("reading"), &attributes).unwrap();
+
+        // both girls in group 0 and both boys in group 1
+        assert_eq!(gender.penalty(&solution, 2), 2.0);
+        // group means 0.5 and 2.5 around a class mean of 1.5 with variance 1.25
+        assert_eq!(reading.penalty(&solution, 2), 0.8);
+
+        solution.insert(StudentId(1), GroupId(1));
+        solution.insert(StudentId(3), GroupId(0));
+        assert_eq!(gender.penalty(&solution, 2), 0.0);
+        assert_eq!(reading.penalty(&solution, 2), 0.0);
+
+        let even = [SizeBounds::exact(2), SizeBounds::exact(2)];
+        assert_eq!(gender.lower_bound(&even, 4), 0.0);
+        // a group of three cannot hold one and a half girls
+        let uneven = [SizeBounds::exact(3), SizeBounds::exact(1)];
+        assert_eq!(gender.lower_bound(&uneven, 4), 0.5);
+        assert_eq!(reading.lower_bound(&uneven, 4), 0.0);
+    }
+
+    #[test]
+    fn test_homogeneity_penalty() {
+        let (attributes, mut solution) = four_students();
+        let objective = |attribute: &str| AttributeObjective {
+            attribute: attribute.to_string(),
+            weight: 1.0,
+            mode: AttributeMode::Homogeneous,
+        };
+        assert!(AttributeTerm::new(&objective("gender"), &attributes).is_err());
+        let reading = AttributeTerm::new(&objective("reading"), &attributes).unwrap();
+
+        // levels 0 and 1 together and 2 and 3 together
+        assert_eq!(reading.penalty(&solution, 2), 0.4);
+
+        solution.insert(StudentId(1), GroupId(1));
+        solution.insert(StudentId(3), GroupId(0));
+        assert_eq!(reading.penalty(&solution, 2), 2.0);
+    }
+}
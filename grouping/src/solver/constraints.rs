@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use super::{default_weight, GroupId, Solution, StudentId};
+
+fn default_max_per_group() -> usize {
+    1
+}
+
+fn default_min_per_group() -> usize {
+    1
+}
+
+/// A set of students of whom at most `max_per_group` may share a group.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SpreadSet {
+    pub students: Vec<StudentId>,
+    #[serde(default = "default_max_per_group")]
+    pub max_per_group: usize,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub hard: bool,
+}
+
+impl SpreadSet {
+    /// The members of the set in each group that holds more than `max_per_group` of them, ordered
+    /// by group.
+    pub fn crowded_groups(&self, solution: &Solution) -> Vec<(GroupId, Vec<StudentId>)> {
+        let mut members: HashMap<&GroupId, Vec<StudentId>> = HashMap::new();
+        for student in &self.students {
+            members
+                .entry(&solution[student])
+                .or_default()
+                .push(student.clone());
+        }
+        let mut crowded: Vec<(GroupId, Vec<StudentId>)> = members
+            .into_iter()
+            .filter(|(_, students)| students.len() > self.max_per_group)
+            .map(|(group, students)| (group.clone(), students))
+            .collect();
+        crowded.sort_by_key(|(group, _)| **group);
+        crowded
+    }
+
+    /// How many members of the set are over the limit, summed over the groups.
+    pub fn excess(&self, solution: &Solution) -> usize {
+        let mut counts: HashMap<&GroupId, usize> = HashMap::new();
+        for student in &self.students {
+            *counts.entry(&solution[student]).or_default() += 1;
+        }
+        counts
+            .values()
+            .map(|&count| count.saturating_sub(self.max_per_group))
+            .sum()
+    }
+}
+
+/// Requires every group to contain at least `min_per_group` students with the given tag.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Coverage {
+    pub tag: String,
+    #[serde(default = "default_min_per_group")]
+    pub min_per_group: usize,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub hard: bool,
+}
+
+impl Coverage {
+    /// The number of tagged students in each group.
+    pub fn counts(
+        &self,
+        solution: &Solution,
+        tagged: &[StudentId],
+        num_groups: usize,
+    ) -> Vec<usize> {
+        let mut counts = vec![0; num_groups];
+        for student in tagged {
+            counts[*solution[student]] += 1;
+        }
+        counts
+    }
+
+    /// How many tagged students are missing, summed over the groups.
+    pub fn shortfall(&self, solution: &Solution, tagged: &[StudentId], num_groups: usize) -> usize {
+        self.counts(solution, tagged, num_groups)
+            .iter()
+            .map(|&count| self.min_per_group.saturating_sub(count))
+            .sum()
+    }
+}
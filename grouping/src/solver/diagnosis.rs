@@ -0,0 +1,624 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use super::{
+    constraints::{Coverage, SpreadSet},
+    problem::Problem,
+    GroupId, RelationshipKind, RelationshipPair, StudentId,
+};
+
+/// How many partial cliques to try before giving up on finding too many mutually separated
+/// students.
+const MAX_CLIQUE_SEARCH_STEPS: usize = 100_000;
+
+/// A constraint taking part in a conflict.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConflictingConstraint {
+    Pair {
+        relationship_pair: RelationshipPair,
+    },
+    SpreadSet {
+        spread_set: SpreadSet,
+    },
+    Coverage {
+        coverage: Coverage,
+    },
+    /// The groups a pinned student is allowed in.
+    Pin {
+        student_id: StudentId,
+        allowed_groups: Vec<GroupId>,
+    },
+    /// The guarantee that a student shares a group with one of their wishes.
+    Guarantee {
+        student_id: StudentId,
+        wishes: Vec<StudentId>,
+    },
+    NumGroups {
+        num_groups: usize,
+    },
+    /// The largest number of students any group may hold.
+    MaxGroupSize {
+        max_size: usize,
+    },
+}
+
+/// Hard constraints that provably cannot all be satisfied, trimmed down to a set in which every
+/// constraint is needed for the conflict.
+#[derive(Debug, serde::Serialize)]
+pub struct Conflict {
+    pub reason: String,
+    pub students: Vec<StudentId>,
+    pub constraints: Vec<ConflictingConstraint>,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Hard constraints conflict: {}", self.reason)
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// Identifies a constraint of the problem while a conflict is being collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Pair(usize),
+    SpreadSet(usize),
+    Coverage(usize),
+    Pin(usize),
+    Guarantee(usize),
+    NumGroups,
+    MaxGroupSize,
+}
+
+/// The hard constraints of a problem as graphs over student indices.
+struct ConstraintGraph<'a> {
+    problem: &'a Problem,
+    index: HashMap<&'a StudentId, usize>,
+    /// Students who must be together, with the constraint joining them.
+    together: Vec<Vec<(usize, Key)>>,
+    /// Students who must be apart, with the constraint separating them.
+    apart: BTreeMap<(usize, usize), Key>,
+}
+
+impl<'a> ConstraintGraph<'a> {
+    fn new(problem: &'a Problem) -> anyhow::Result<Self> {
+        let index: HashMap<&StudentId, usize> = problem
+            .students
+            .iter()
+            .enumerate()
+            .map(|(i, student)| (student, i))
+            .collect();
+        let mut together = vec![vec![]; problem.students.len()];
+        let mut apart = BTreeMap::new();
+        for (i, pair) in problem.relationship_pairs.iter().enumerate() {
+            if !pair.hard {
+                continue;
+            }
+            let (Some(&first), Some(&second)) = (
+                index.get(&pair.first_student_id),
+                index.get(&pair.second_student_id),
+            ) else {
+                return Err(anyhow::anyhow!("Invalid student id in relationship pair"));
+            };
+            match pair.kind {
+                RelationshipKind::Together => {
+                    together[first].push((second, Key::Pair(i)));
+                    together[second].push((first, Key::Pair(i)));
+                }
+                RelationshipKind::Apart => {
+                    apart.entry(edge(first, second)).or_insert(Key::Pair(i));
+                }
+            }
+        }
+        for (i, spread_set) in problem.spread_sets.iter().enumerate() {
+            if !spread_set.hard || spread_set.max_per_group != 1 {
+                continue;
+            }
+            for (j, first) in spread_set.students.iter().enumerate() {
+                for second in &spread_set.students[j + 1..] {
+                    if first != second {
+                        apart
+                            .entry(edge(index[first], index[second]))
+                            .or_insert(Key::SpreadSet(i));
+                    }
+                }
+            }
+        }
+        Ok(ConstraintGraph {
+            problem,
+            index,
+            together,
+            apart,
+        })
+    }
+
+    /// The students that must be together with `start`, in breadth-first order.
+    fn component(&self, start: usize) -> Vec<usize> {
+        let mut visited = HashSet::from([start]);
+        let mut order = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some(student) = queue.pop_front() {
+            for &(neighbour, _) in &self.together[student] {
+                if visited.insert(neighbour) {
+                    order.push(neighbour);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        order
+    }
+
+    /// The together constraints on a shortest chain from one student to another.
+    fn together_path(&self, from: usize, to: usize) -> Vec<Key> {
+        let mut previous: HashMap<usize, (usize, Key)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(student) = queue.pop_front() {
+            if student == to {
+                break;
+            }
+            for &(neighbour, key) in &self.together[student] {
+                if neighbour != from && !previous.contains_key(&neighbour) {
+                    previous.insert(neighbour, (student, key));
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        let mut path = vec![];
+        let mut student = to;
+        while let Some(&(before, key)) = previous.get(&student) {
+            path.push(key);
+            student = before;
+        }
+        path
+    }
+
+    /// The together constraints connecting the first student to each of the others.
+    fn together_paths(&self, students: &[usize]) -> Vec<Key> {
+        students[1..]
+            .iter()
+            .flat_map(|&student| self.together_path(students[0], student))
+            .collect()
+    }
+
+    fn conflict(&self, reason: String, students: &[usize], keys: Vec<Key>) -> Conflict {
+        let mut unique: Vec<Key> = vec![];
+        for key in keys {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        let problem = self.problem;
+        let constraints = unique
+            .into_iter()
+            .map(|key| match key {
+                Key::Pair(i) => ConflictingConstraint::Pair {
+                    relationship_pair: problem.relationship_pairs[i].clone(),
+                },
+                Key::SpreadSet(i) => ConflictingConstraint::SpreadSet {
+                    spread_set: problem.spread_sets[i].clone(),
+                },
+                Key::Coverage(i) => ConflictingConstraint::Coverage {
+                    coverage: problem.coverage[i].clone(),
+                },
+                Key::Pin(student) => {
+                    let student_id = problem.students[student].clone();
+                    ConflictingConstraint::Pin {
+                        allowed_groups: problem.allowed_groups[&student_id].clone(),
+                        student_id,
+                    }
+                }
+                Key::Guarantee(i) => {
+                    let preference = &problem.preferences.students[i];
+                    ConflictingConstraint::Guarantee {
+                        student_id: preference.student_id.clone(),
+                        wishes: preference.wishes.clone(),
+                    }
+                }
+                Key::NumGroups => ConflictingConstraint::NumGroups {
+                    num_groups: problem.num_groups,
+                },
+                Key::MaxGroupSize => ConflictingConstraint::MaxGroupSize {
+                    max_size: max_group_size(problem),
+                },
+            })
+            .collect();
+        Conflict {
+            reason,
+            students: students
+                .iter()
+                .map(|&student| problem.students[student].clone())
+                .collect(),
+            constraints,
+        }
+    }
+}
+
+fn edge(first: usize, second: usize) -> (usize, usize) {
+    (first.min(second), first.max(second))
+}
+
+fn max_group_size(problem: &Problem) -> usize {
+    problem
+        .group_sizes
+        .iter()
+        .map(|bounds| bounds.max)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Looks for hard constraints that no grouping can satisfy, such as more mutually separated
+/// students than there are groups, or a chain of students who must be together that is longer
+/// than the largest group. Fails with a [`Conflict`] if it finds one.
+pub fn diagnose(problem: &Problem) -> anyhow::Result<()> {
+    let graph = ConstraintGraph::new(problem)?;
+    check_num_groups(&graph)?;
+    check_together_components(&graph)?;
+    check_spread_sets(&graph)?;
+    check_coverage(&graph)?;
+    check_pinned_pairs(&graph)?;
+    check_guarantee(&graph)?;
+    check_separated_cliques(&graph)?;
+    Ok(())
+}
+
+fn check_num_groups(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    if graph.problem.num_groups == 0 {
+        return Err(graph.conflict(
+            "There must be at least one group".to_string(),
+            &[],
+            vec![Key::NumGroups],
+        ));
+    }
+    Ok(())
+}
+
+fn check_together_components(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    let max_size = max_group_size(problem);
+    let mut seen = vec![false; problem.students.len()];
+    for start in 0..problem.students.len() {
+        if seen[start] || graph.together[start].is_empty() {
+            continue;
+        }
+        let component = graph.component(start);
+        for &student in &component {
+            seen[student] = true;
+        }
+
+        if component.len() > max_size {
+            let students = &component[..=max_size];
+            let mut keys = graph.together_paths(students);
+            keys.push(Key::MaxGroupSize);
+            return Err(graph.conflict(
+                format!(
+                    "{} students must be together but no group holds more than {}",
+                    component.len(),
+                    max_size
+                ),
+                students,
+                keys,
+            ));
+        }
+
+        let members: HashSet<usize> = component.iter().copied().collect();
+        for (&(first, second), &key) in &graph.apart {
+            if members.contains(&first) && members.contains(&second) {
+                let mut keys = graph.together_path(first, second);
+                keys.push(key);
+                return Err(graph.conflict(
+                    format!(
+                        "Students {} and {} must be both together and apart",
+                        problem.students[first], problem.students[second]
+                    ),
+                    &[first, second],
+                    keys,
+                ));
+            }
+        }
+
+        for (i, spread_set) in problem.spread_sets.iter().enumerate() {
+            if !spread_set.hard {
+                continue;
+            }
+            let inside: Vec<usize> = spread_set
+                .students
+                .iter()
+                .map(|student| graph.index[student])
+                .filter(|student| members.contains(student))
+                .collect();
+            if inside.len() > spread_set.max_per_group {
+                let students = &inside[..=spread_set.max_per_group];
+                let mut keys = graph.together_paths(students);
+                keys.push(Key::SpreadSet(i));
+                return Err(graph.conflict(
+                    format!(
+                        "{} students of a spread set must be together but at most {} may share a group",
+                        students.len(),
+                        spread_set.max_per_group
+                    ),
+                    students,
+                    keys,
+                ));
+            }
+        }
+
+        let mut pinned: Vec<usize> = vec![];
+        let mut common: Option<HashSet<&GroupId>> = None;
+        for &student in &component {
+            let Some(allowed) = problem.allowed_groups.get(&problem.students[student]) else {
+                continue;
+            };
+            pinned.push(student);
+            let allowed: HashSet<&GroupId> = allowed.iter().collect();
+            let remaining: HashSet<&GroupId> = match common {
+                Some(common) => common.intersection(&allowed).copied().collect(),
+                None => allowed,
+            };
+            if remaining.is_empty() {
+                let mut keys = graph.together_paths(&pinned);
+                keys.extend(pinned.iter().map(|&student| Key::Pin(student)));
+                return Err(graph.conflict(
+                    "Students who must be together are pinned to different groups".to_string(),
+                    &pinned,
+                    keys,
+                ));
+            }
+            common = Some(remaining);
+        }
+    }
+    Ok(())
+}
+
+fn check_spread_sets(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    for (i, spread_set) in problem.spread_sets.iter().enumerate() {
+        let members: HashSet<&StudentId> = spread_set.students.iter().collect();
+        if spread_set.hard && members.len() > spread_set.max_per_group * problem.num_groups {
+            let students: Vec<usize> = spread_set
+                .students
+                .iter()
+                .map(|student| graph.index[student])
+                .collect();
+            return Err(graph.conflict(
+                format!(
+                    "{} students of a spread set with at most {} per group do not fit into {} groups",
+                    members.len(),
+                    spread_set.max_per_group,
+                    problem.num_groups
+                ),
+                &students,
+                vec![Key::SpreadSet(i), Key::NumGroups],
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_coverage(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    for (i, coverage) in problem.coverage.iter().enumerate() {
+        let tagged = problem.tagged_students(&coverage.tag);
+        if coverage.hard && tagged.len() < coverage.min_per_group * problem.num_groups {
+            let students: Vec<usize> = tagged.iter().map(|student| graph.index[student]).collect();
+            return Err(graph.conflict(
+                format!(
+                    "{} groups need {} students tagged {} each but only {} have the tag",
+                    problem.num_groups,
+                    coverage.min_per_group,
+                    coverage.tag,
+                    tagged.len()
+                ),
+                &students,
+                vec![Key::Coverage(i), Key::NumGroups],
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_pinned_pairs(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    let pinned_group = |student: usize| match problem.allowed_groups.get(&problem.students[student])
+    {
+        Some(groups) if groups.len() == 1 => Some(&groups[0]),
+        _ => None,
+    };
+    for (&(first, second), &key) in &graph.apart {
+        if let (Some(first_group), Some(second_group)) = (pinned_group(first), pinned_group(second))
+        {
+            if first_group == second_group {
+                return Err(graph.conflict(
+                    format!(
+                        "Students {} and {} must be apart but are both pinned to group {}",
+                        problem.students[first], problem.students[second], first_group
+                    ),
+                    &[first, second],
+                    vec![key, Key::Pin(first), Key::Pin(second)],
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_guarantee(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    if !problem
+        .preferences
+        .guarantee
+        .as_ref()
+        .is_some_and(|guarantee| guarantee.hard)
+    {
+        return Ok(());
+    }
+    for (i, preference) in problem.preferences.students.iter().enumerate() {
+        let student = graph.index[&preference.student_id];
+        let separations: Option<Vec<Key>> = preference
+            .wishes
+            .iter()
+            .map(|wish| graph.apart.get(&edge(student, graph.index[wish])).copied())
+            .collect();
+        if let Some(mut keys) = separations.filter(|keys| !keys.is_empty()) {
+            let mut students = vec![student];
+            students.extend(preference.wishes.iter().map(|wish| graph.index[wish]));
+            keys.push(Key::Guarantee(i));
+            return Err(graph.conflict(
+                format!(
+                    "Student {} must be kept apart from every student they wished for",
+                    preference.student_id
+                ),
+                &students,
+                keys,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Finds more students than there are groups who must all be kept apart from each other.
+fn check_separated_cliques(graph: &ConstraintGraph) -> Result<(), Conflict> {
+    let problem = graph.problem;
+    let target = problem.num_groups + 1;
+    let mut neighbours: Vec<HashSet<usize>> = vec![HashSet::new(); problem.students.len()];
+    for &(first, second) in graph.apart.keys() {
+        neighbours[first].insert(second);
+        neighbours[second].insert(first);
+    }
+    // only students separated from at least `num_groups` others can be part of the clique
+    let candidates: Vec<usize> = (0..problem.students.len())
+        .filter(|&student| neighbours[student].len() + 1 >= target)
+        .collect();
+    let mut clique = vec![];
+    let mut steps = 0;
+    if !extend_clique(&mut clique, candidates, &neighbours, target, &mut steps) {
+        return Ok(());
+    }
+    let mut keys = vec![];
+    for (i, &first) in clique.iter().enumerate() {
+        for &second in &clique[i + 1..] {
+            keys.push(graph.apart[&edge(first, second)]);
+        }
+    }
+    keys.push(Key::NumGroups);
+    Err(graph.conflict(
+        format!(
+            "{} students must all be apart but there are only {} groups",
+            clique.len(),
+            problem.num_groups
+        ),
+        &clique,
+        keys,
+    ))
+}
+
+fn extend_clique(
+    clique: &mut Vec<usize>,
+    candidates: Vec<usize>,
+    neighbours: &[HashSet<usize>],
+    target: usize,
+    steps: &mut usize,
+) -> bool {
+    if clique.len() == target {
+        return true;
+    }
+    *steps += 1;
+    if *steps > MAX_CLIQUE_SEARCH_STEPS {
+        return false;
+    }
+    for (i, &student) in candidates.iter().enumerate() {
+        if clique.len() + candidates.len() - i < target {
+            return false;
+        }
+        let remaining: Vec<usize> = candidates[i + 1..]
+            .iter()
+            .copied()
+            .filter(|other| neighbours[student].contains(other))
+            .collect();
+        clique.push(student);
+        if extend_clique(clique, remaining, neighbours, target, steps) {
+            return true;
+        }
+        clique.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(first: usize, second: usize, kind: RelationshipKind) -> RelationshipPair {
+        RelationshipPair {
+            first_student_id: first.into(),
+            second_student_id: second.into(),
+            kind,
+            weight: 1.0,
+            hard: true,
+        }
+    }
+
+    fn find_conflict(problem: &Problem) -> Conflict {
+        diagnose(problem).unwrap_err().downcast().unwrap()
+    }
+
+    #[test]
+    fn test_diagnose_separated_clique() {
+        let students: Vec<StudentId> = (0..12).map(StudentId).collect();
+        let mut pairs = vec![];
+        for first in 0..5 {
+            for second in first + 1..5 {
+                pairs.push(pair(first, second, RelationshipKind::Apart));
+            }
+        }
+        pairs.push(pair(6, 7, RelationshipKind::Apart));
+        let conflict = find_conflict(&Problem::new(students.clone(), 4, pairs.clone()));
+        assert_eq!(conflict.students.len(), 5);
+        // the ten pairs among the five students and the number of groups
+        assert_eq!(conflict.constraints.len(), 11);
+
+        assert!(diagnose(&Problem::new(students, 5, pairs)).is_ok());
+    }
+
+    #[test]
+    fn test_diagnose_together_and_apart() {
+        let students: Vec<StudentId> = (0..12).map(StudentId).collect();
+        let pairs = vec![
+            pair(0, 1, RelationshipKind::Together),
+            pair(1, 2, RelationshipKind::Together),
+            pair(2, 3, RelationshipKind::Together),
+            pair(5, 6, RelationshipKind::Together),
+            pair(0, 2, RelationshipKind::Apart),
+        ];
+        let conflict = find_conflict(&Problem::new(students, 3, pairs));
+        assert_eq!(conflict.students, vec![StudentId(0), StudentId(2)]);
+        assert_eq!(conflict.constraints.len(), 3);
+    }
+
+    #[test]
+    fn test_diagnose_together_chain_too_long() {
+        let students: Vec<StudentId> = (0..12).map(StudentId).collect();
+        let pairs = (0..6)
+            .map(|student| pair(student, student + 1, RelationshipKind::Together))
+            .collect();
+        let conflict = find_conflict(&Problem::new(students, 3, pairs));
+        // five students and the four pairs joining them are enough for groups of four
+        assert_eq!(conflict.students.len(), 5);
+        assert_eq!(conflict.constraints.len(), 5);
+    }
+
+    #[test]
+    fn test_diagnose_no_groups() {
+        let students: Vec<StudentId> = (0..12).map(StudentId).collect();
+        let conflict = find_conflict(&Problem::new(students, 0, vec![]));
+        assert!(conflict.students.is_empty());
+    }
+
+    #[test]
+    fn test_diagnose_pair_with_unknown_student() {
+        let students: Vec<StudentId> = (0..12).map(StudentId).collect();
+        let pairs = vec![pair(0, 99, RelationshipKind::Apart)];
+        let error = diagnose(&Problem::new(students, 3, pairs)).unwrap_err();
+        assert!(error.downcast_ref::<Conflict>().is_none());
+    }
+}
@@ -0,0 +1,715 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::problem::Problem;
+use crate::solver::{
+    attributes::{GroupStats, StudentValue},
+    objective::{objective, penalty_per_violation},
+    GroupId, Solution, StudentId,
+};
+
+/// A change to a solution: each listed unit goes to the group given with it. Units and groups are
+/// given by index.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Move {
+    pub changes: Vec<(usize, usize)>,
+}
+
+impl Move {
+    pub fn unit(unit: usize, group: usize) -> Self {
+        Move {
+            changes: vec![(unit, group)],
+        }
+    }
+
+    pub fn and(mut self, other: Move) -> Self {
+        self.changes.extend(other.changes);
+        self
+    }
+}
+
+/// How much a move changed the objective and the number of violated hard constraints.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Delta {
+    pub score: f64,
+    pub hard_violations: i64,
+}
+
+/// Everything a student's group has a bearing on, as indices into the problem. A student listed
+/// twice in a spread set or under a tag appears twice here too.
+#[derive(Debug, Default)]
+struct StudentTerms {
+    pairs: Vec<usize>,
+    spread_sets: Vec<usize>,
+    coverage: Vec<usize>,
+    /// Preferences of the student and of those who wish for them.
+    preferences: Vec<usize>,
+    repeats: Vec<usize>,
+    attributes: Vec<(usize, StudentValue)>,
+}
+
+/// The terms of the objective a move can change.
+#[derive(Debug, Default)]
+struct Affected {
+    groups: Vec<usize>,
+    pairs: Vec<usize>,
+    spread_sets: Vec<usize>,
+    coverage: Vec<usize>,
+    preferences: Vec<usize>,
+    repeats: Vec<usize>,
+}
+
+/// A set of indices below a fixed bound, with constant-time insertion, removal and random access.
+#[derive(Debug, Clone)]
+struct IndexSet {
+    /// The indices in the set, in no particular order.
+    members: Vec<usize>,
+    /// Where each index is in `members`, if it is in the set.
+    position: Vec<Option<usize>>,
+}
+
+impl IndexSet {
+    fn new(bound: usize) -> Self {
+        IndexSet {
+            members: vec![],
+            position: vec![None; bound],
+        }
+    }
+
+    /// Puts the index in the set or takes it out.
+    fn set(&mut self, index: usize, present: bool) {
+        match (present, self.position[index]) {
+            (true, None) => {
+                self.position[index] = Some(self.members.len());
+                self.members.push(index);
+            }
+            (false, Some(position)) => {
+                self.members.swap_remove(position);
+                if let Some(&moved) = self.members.get(position) {
+                    self.position[moved] = Some(position);
+                }
+                self.position[index] = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns true if the student of a preference shares a group with none of their wishes.
+fn is_isolated((student, wishes): &(usize, Vec<usize>), assignment: &[usize]) -> bool {
+    let group = assignment[*student];
+    !wishes.is_empty() && wishes.iter().all(|&wish| assignment[wish] != group)
+}
+
+/// The students of each unit and the constraints as indices, which stay the same while a solution
+/// changes.
+#[derive(Debug)]
+struct Layout {
+    /// The students of each unit.
+    unit_members: Vec<Vec<usize>>,
+    unit_of: Vec<usize>,
+    /// The groups each unit may be placed in, for units with a pinned student.
+    unit_allowed: Vec<Option<Vec<bool>>>,
+    /// The two students of each relationship pair.
+    pairs: Vec<(usize, usize)>,
+    /// Each student with wishes, and their wishes.
+    wishes: Vec<(usize, Vec<usize>)>,
+    /// The two students of each pair from the history.
+    repeats: Vec<(usize, usize)>,
+    terms: Vec<StudentTerms>,
+    has_hard_constraints: bool,
+}
+
+/// A solution in a compact form, with running totals of everything the objective depends on, so
+/// that a move is scored by looking only at the constraints and groups it touches, and is applied
+/// and undone in place.
+///
+/// Students are numbered by their position in `problem.students` and units as in
+/// `problem.units`; [`StudentId`]s are only looked up when a state is made from a [`Solution`] or
+/// turned back into one. Cloning a state is cheap next to making one.
+#[derive(Clone)]
+pub(crate) struct State<'a> {
+    problem: &'a Problem,
+    layout: Arc<Layout>,
+    /// The group of each student.
+    assignment: Vec<usize>,
+    unit_group: Vec<usize>,
+    /// The units in each group, in no particular order.
+    group_units: Vec<Vec<usize>>,
+    /// Where each unit is in the list of its group.
+    unit_position: Vec<usize>,
+    group_sizes: Vec<usize>,
+    /// Members of each spread set in each group.
+    spread_counts: Vec<Vec<usize>>,
+    /// Tagged students of each coverage constraint in each group.
+    coverage_counts: Vec<Vec<usize>>,
+    /// Totals of each attribute term in each group.
+    attribute_stats: Vec<Vec<GroupStats>>,
+    /// The preferences of students who share a group with none of their wishes.
+    isolated: IndexSet,
+    score: f64,
+}
+
+impl<'a> State<'a> {
+    /// Takes on the solution, which must place the students of each unit together.
+    pub fn new(problem: &'a Problem, solution: &Solution) -> anyhow::Result<Self> {
+        let score = objective(solution, problem)?;
+        let num_groups = problem.num_groups;
+        let index: HashMap<&StudentId, usize> = problem
+            .students
+            .iter()
+            .enumerate()
+            .map(|(i, student)| (student, i))
+            .collect();
+        let assignment: Vec<usize> = problem
+            .students
+            .iter()
+            .map(|student| *solution[student])
+            .collect();
+        let mut terms: Vec<StudentTerms> = problem
+            .students
+            .iter()
+            .map(|_| StudentTerms::default())
+            .collect();
+        let mut group_sizes = vec![0; num_groups];
+        for &group in &assignment {
+            group_sizes[group] += 1;
+        }
+
+        let units = &problem.units;
+        let unit_members: Vec<Vec<usize>> = (0..units.len())
+            .map(|unit| {
+                units
+                    .members(unit)
+                    .iter()
+                    .map(|student| index[student])
+                    .collect()
+            })
+            .collect();
+        let mut unit_of = vec![0; assignment.len()];
+        for (unit, members) in unit_members.iter().enumerate() {
+            for &student in members {
+                unit_of[student] = unit;
+            }
+        }
+        let unit_allowed = (0..units.len())
+            .map(|unit| {
+                let pinned = units
+                    .members(unit)
+                    .iter()
+                    .any(|student| problem.allowed_groups.contains_key(student));
+                pinned.then(|| {
+                    (0..num_groups)
+                        .map(|group| problem.allows_unit(unit, &GroupId(group)))
+                        .collect()
+                })
+            })
+            .collect();
+        let unit_group: Vec<usize> = unit_members
+            .iter()
+            .map(|members| assignment[members[0]])
+            .collect();
+        let mut group_units = vec![vec![]; num_groups];
+        let mut unit_position = vec![0; unit_group.len()];
+        for (unit, &group) in unit_group.iter().enumerate() {
+            unit_position[unit] = group_units[group].len();
+            group_units[group].push(unit);
+        }
+
+        let pairs = problem
+            .relationship_pairs
+            .iter()
+            .enumerate()
+            .map(|(i, pair)| {
+                let pair = (
+                    index[&pair.first_student_id],
+                    index[&pair.second_student_id],
+                );
+                terms[pair.0].pairs.push(i);
+                terms[pair.1].pairs.push(i);
+                pair
+            })
+            .collect();
+        let mut spread_counts = vec![vec![0; num_groups]; problem.spread_sets.len()];
+        for (i, spread_set) in problem.spread_sets.iter().enumerate() {
+            for student in &spread_set.students {
+                let student = index[student];
+                terms[student].spread_sets.push(i);
+                spread_counts[i][assignment[student]] += 1;
+            }
+        }
+        let mut coverage_counts = vec![vec![0; num_groups]; problem.coverage.len()];
+        for (i, coverage) in problem.coverage.iter().enumerate() {
+            for student in problem.tagged_students(&coverage.tag) {
+                let student = index[student];
+                terms[student].coverage.push(i);
+                coverage_counts[i][assignment[student]] += 1;
+            }
+        }
+        let wishes: Vec<(usize, Vec<usize>)> = problem
+            .preferences
+            .students
+            .iter()
+            .enumerate()
+            .map(|(i, preference)| {
+                let student = index[&preference.student_id];
+                let wishes: Vec<usize> = preference.wishes.iter().map(|wish| index[wish]).collect();
+                for &student in std::iter::once(&student).chain(&wishes) {
+                    terms[student].preferences.push(i);
+                }
+                (student, wishes)
+            })
+            .collect();
+        let repeats = problem
+            .repeat_penalties
+            .pairs()
+            .iter()
+            .enumerate()
+            .map(|(i, ((first, second), _))| {
+                let pair = (index[first], index[second]);
+                terms[pair.0].repeats.push(i);
+                terms[pair.1].repeats.push(i);
+                pair
+            })
+            .collect();
+        let mut isolated = IndexSet::new(wishes.len());
+        for (i, preference) in wishes.iter().enumerate() {
+            isolated.set(i, is_isolated(preference, &assignment));
+        }
+        let mut attribute_stats = vec![];
+        for (i, term) in problem.attribute_terms.iter().enumerate() {
+            let mut stats = vec![term.empty_stats(); num_groups];
+            for (student, value) in term.student_values() {
+                let student = index[student];
+                terms[student].attributes.push((i, value));
+                stats[assignment[student]].add(value);
+            }
+            attribute_stats.push(stats);
+        }
+
+        Ok(State {
+            problem,
+            layout: Arc::new(Layout {
+                unit_members,
+                unit_of,
+                unit_allowed,
+                pairs,
+                wishes,
+                repeats,
+                terms,
+                has_hard_constraints: problem.has_hard_constraints(),
+            }),
+            assignment,
+            unit_group,
+            group_units,
+            unit_position,
+            group_sizes,
+            spread_counts,
+            coverage_counts,
+            attribute_stats,
+            isolated,
+            score,
+        })
+    }
+
+    /// The group of each student.
+    pub fn assignment(&self) -> &[usize] {
+        &self.assignment
+    }
+
+    /// Turns an assignment of this state back into a solution.
+    pub fn solution_of(&self, assignment: &[usize]) -> Solution {
+        self.problem
+            .students
+            .iter()
+            .zip(assignment)
+            .map(|(student, &group)| (student.clone(), GroupId(group)))
+            .collect()
+    }
+
+    pub fn group_sizes(&self) -> &[usize] {
+        &self.group_sizes
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn unit_of(&self, student: usize) -> usize {
+        self.layout.unit_of[student]
+    }
+
+    pub fn unit_size(&self, unit: usize) -> usize {
+        self.layout.unit_members[unit].len()
+    }
+
+    pub fn unit_group(&self, unit: usize) -> usize {
+        self.unit_group[unit]
+    }
+
+    pub fn units_in(&self, group: usize) -> &[usize] {
+        &self.group_units[group]
+    }
+
+    /// Returns true if every student of the unit may be placed in the group.
+    pub fn allows(&self, unit: usize, group: usize) -> bool {
+        self.layout.unit_allowed[unit]
+            .as_ref()
+            .is_none_or(|allowed| allowed[group])
+    }
+
+    /// Returns true if the problem has any hard constraint, which moves must then not break.
+    pub fn has_hard_constraints(&self) -> bool {
+        self.layout.has_hard_constraints
+    }
+
+    /// A student with wishes, and their wishes.
+    pub fn wishes(&self, preference: usize) -> (usize, &[usize]) {
+        let (student, wishes) = &self.layout.wishes[preference];
+        (*student, wishes)
+    }
+
+    /// The preferences of students who share a group with none of their wishes, in no particular
+    /// order.
+    pub fn isolated(&self) -> &[usize] {
+        &self.isolated.members
+    }
+
+    /// Makes the move and returns the move that undoes it, with the change in score.
+    pub fn apply(&mut self, change: &Move) -> (Move, Delta) {
+        let affected = self.affected(change);
+        let (score_before, hard_before) = self.local_score(&affected);
+        let inverse = self.reassign(change);
+        let (score_after, hard_after) = self.local_score(&affected);
+        let delta = Delta {
+            score: score_after - score_before,
+            hard_violations: hard_after as i64 - hard_before as i64,
+        };
+        self.score += delta.score;
+        (inverse, delta)
+    }
+
+    /// Takes back a move made by [`State::apply`], given the inverse move and delta it returned.
+    pub fn undo(&mut self, inverse: &Move, delta: Delta) {
+        self.reassign(inverse);
+        self.score -= delta.score;
+    }
+
+    /// Moves the units and updates the running totals, returning the inverse move.
+    fn reassign(&mut self, change: &Move) -> Move {
+        let mut inverse = Move::default();
+        for &(unit, to) in &change.changes {
+            let from = self.unit_group[unit];
+            if from == to {
+                continue;
+            }
+            let position = self.unit_position[unit];
+            self.group_units[from].swap_remove(position);
+            if let Some(&moved) = self.group_units[from].get(position) {
+                self.unit_position[moved] = position;
+            }
+            self.unit_position[unit] = self.group_units[to].len();
+            self.group_units[to].push(unit);
+            self.unit_group[unit] = to;
+
+            for &student in &self.layout.unit_members[unit] {
+                self.group_sizes[from] -= 1;
+                self.group_sizes[to] += 1;
+                let terms = &self.layout.terms[student];
+                for &i in &terms.spread_sets {
+                    self.spread_counts[i][from] -= 1;
+                    self.spread_counts[i][to] += 1;
+                }
+                for &i in &terms.coverage {
+                    self.coverage_counts[i][from] -= 1;
+                    self.coverage_counts[i][to] += 1;
+                }
+                for &(i, value) in &terms.attributes {
+                    self.attribute_stats[i][from].remove(value);
+                    self.attribute_stats[i][to].add(value);
+                }
+                self.assignment[student] = to;
+            }
+            inverse.changes.push((unit, from));
+        }
+        for &(unit, _) in &change.changes {
+            for &student in &self.layout.unit_members[unit] {
+                for &i in &self.layout.terms[student].preferences {
+                    let isolated = is_isolated(&self.layout.wishes[i], &self.assignment);
+                    self.isolated.set(i, isolated);
+                }
+            }
+        }
+        inverse.changes.reverse();
+        inverse
+    }
+
+    fn affected(&self, change: &Move) -> Affected {
+        let mut affected = Affected::default();
+        for &(unit, to) in &change.changes {
+            affected.groups.push(self.unit_group[unit]);
+            affected.groups.push(to);
+            for &student in &self.layout.unit_members[unit] {
+                let terms = &self.layout.terms[student];
+                affected.pairs.extend(&terms.pairs);
+                affected.spread_sets.extend(&terms.spread_sets);
+                affected.coverage.extend(&terms.coverage);
+                affected.preferences.extend(&terms.preferences);
+                affected.repeats.extend(&terms.repeats);
+            }
+        }
+        for indices in [
+            &mut affected.groups,
+            &mut affected.pairs,
+            &mut affected.spread_sets,
+            &mut affected.coverage,
+            &mut affected.preferences,
+            &mut affected.repeats,
+        ] {
+            indices.sort_unstable();
+            indices.dedup();
+        }
+        affected
+    }
+
+    /// The part of the objective, and the number of hard violations, due to the affected terms.
+    fn local_score(&self, affected: &Affected) -> (f64, usize) {
+        let problem = self.problem;
+        let assignment = &self.assignment;
+        let num_groups = problem.num_groups;
+        let mut score = 0.0;
+        let mut hard_violations = 0;
+
+        for &i in &affected.pairs {
+            let pair = &problem.relationship_pairs[i];
+            let (first, second) = self.layout.pairs[i];
+            if pair.is_violated(&GroupId(assignment[first]), &GroupId(assignment[second])) {
+                score += penalty_per_violation(pair.hard, pair.weight);
+                hard_violations += pair.hard as usize;
+            }
+        }
+        for &group in &affected.groups {
+            let size = self.group_sizes[group];
+            score += (problem.group_sizes[group].distance(size) as f64).powi(2) / num_groups as f64;
+            for &i in &affected.spread_sets {
+                let spread_set = &problem.spread_sets[i];
+                let excess = self.spread_counts[i][group].saturating_sub(spread_set.max_per_group);
+                score += excess as f64 * penalty_per_violation(spread_set.hard, spread_set.weight);
+                if spread_set.hard {
+                    hard_violations += excess;
+                }
+            }
+            for &i in &affected.coverage {
+                let coverage = &problem.coverage[i];
+                let shortfall = coverage
+                    .min_per_group
+                    .saturating_sub(self.coverage_counts[i][group]);
+                score += shortfall as f64 * penalty_per_violation(coverage.hard, coverage.weight);
+                if coverage.hard {
+                    hard_violations += shortfall;
+                }
+            }
+            for (term, stats) in problem.attribute_terms.iter().zip(&self.attribute_stats) {
+                score += term.group_penalty(&stats[group], size, assignment.len(), num_groups);
+            }
+        }
+        let preferences = &problem.preferences;
+        for &i in &affected.preferences {
+            let (student, wishes) = &self.layout.wishes[i];
+            let group = assignment[*student];
+            score += preferences.wish_penalty(wishes.iter().map(|&wish| assignment[wish] == group));
+            if let Some(guarantee) = &preferences.guarantee {
+                if is_isolated(&self.layout.wishes[i], assignment) {
+                    score += penalty_per_violation(guarantee.hard, guarantee.weight);
+                    hard_violations += guarantee.hard as usize;
+                }
+            }
+        }
+        let repeats = problem.repeat_penalties.pairs();
+        for &i in &affected.repeats {
+            let (first, second) = self.layout.repeats[i];
+            if assignment[first] == assignment[second] {
+                score += repeats[i].1;
+            }
+        }
+        (score, hard_violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::solver::{
+        attributes::{AttributeMode, AttributeObjective, AttributeValue},
+        constraints::{Coverage, SpreadSet},
+        history::History,
+        preferences::{Guarantee, Preference, Preferences},
+        RelationshipKind, RelationshipPair,
+    };
+
+    use super::*;
+    use crate::solver::group_move;
+
+    /// Counts how badly the solution breaks the hard constraints: one for each violated pair,
+    /// one for each student over the limit of a spread set, one for each tagged student missing
+    /// from a group, and one for each isolated student.
+    fn count_hard_violations(solution: &Solution, problem: &Problem) -> usize {
+        let pairs = problem
+            .relationship_pairs
+            .iter()
+            .filter(|restriction| {
+                restriction.hard
+                    && restriction.is_violated(
+                        &solution[&restriction.first_student_id],
+                        &solution[&restriction.second_student_id],
+                    )
+            })
+            .count();
+        let spread_sets: usize = problem
+            .spread_sets
+            .iter()
+            .filter(|spread_set| spread_set.hard)
+            .map(|spread_set| spread_set.excess(solution))
+            .sum();
+        let coverage: usize = problem
+            .coverage
+            .iter()
+            .filter(|coverage| coverage.hard)
+            .map(|coverage| {
+                coverage.shortfall(
+                    solution,
+                    problem.tagged_students(&coverage.tag),
+                    problem.num_groups,
+                )
+            })
+            .sum();
+        let isolated = match &problem.preferences.guarantee {
+            Some(guarantee) if guarantee.hard => {
+                problem.preferences.isolated_students(solution).count()
+            }
+            _ => 0,
+        };
+        pairs + spread_sets + coverage + isolated
+    }
+
+    #[test]
+    fn test_delta_matches_objective() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let students: Vec<StudentId> = (0..20).map(StudentId).collect();
+        let pairs = (0..15)
+            .map(|i| RelationshipPair {
+                first_student_id: (i % 20).into(),
+                second_student_id: ((i * 7 + 3) % 20).into(),
+                kind: if i % 3 == 0 {
+                    RelationshipKind::Together
+                } else {
+                    RelationshipKind::Apart
+                },
+                weight: 1.5,
+                hard: i % 4 == 0,
+            })
+            .collect();
+        let attributes = students
+            .iter()
+            .map(|student| {
+                let attributes = HashMap::from([
+                    (
+                        "level".to_string(),
+                        AttributeValue::Numeric((**student % 5) as f64),
+                    ),
+                    (
+                        "house".to_string(),
+                        AttributeValue::Categorical((**student % 3).to_string()),
+                    ),
+                ]);
+                (student.clone(), attributes)
+            })
+            .collect();
+        let objectives = [
+            ("level", AttributeMode::Balance),
+            ("level", AttributeMode::Homogeneous),
+            ("house", AttributeMode::Balance),
+        ]
+        .map(|(attribute, mode)| AttributeObjective {
+            attribute: attribute.to_string(),
+            weight: 0.5,
+            mode,
+        });
+        let tags = HashMap::from([("reader".to_string(), (0..6).map(StudentId).collect())]);
+        let history = History {
+            groupings: vec![vec![
+                (0..10).map(StudentId).collect(),
+                (10..20).map(StudentId).collect(),
+            ]],
+            decay: 0.5,
+            weight: 0.3,
+        };
+        let preferences = Preferences {
+            students: (0..10)
+                .map(|i| Preference {
+                    student_id: i.into(),
+                    wishes: vec![((i + 5) % 20).into(), ((i + 11) % 20).into()],
+                })
+                .collect(),
+            weight: 0.7,
+            guarantee: Some(Guarantee {
+                weight: 1.0,
+                hard: true,
+            }),
+        };
+        let problem = Problem::new(students.clone(), 4, pairs)
+            .with_attributes(&attributes, &objectives)
+            .unwrap()
+            .with_spread_sets(vec![SpreadSet {
+                students: (12..18).map(StudentId).collect(),
+                max_per_group: 1,
+                weight: 2.0,
+                hard: false,
+            }])
+            .unwrap()
+            .with_coverage(
+                tags,
+                vec![Coverage {
+                    tag: "reader".to_string(),
+                    min_per_group: 2,
+                    weight: 1.0,
+                    hard: true,
+                }],
+            )
+            .unwrap()
+            .with_history(&history)
+            .unwrap()
+            .with_preferences(preferences)
+            .unwrap();
+
+        let solution = group_move::random_solution(&problem, &mut rng).unwrap();
+        let mut state = State::new(&problem, &solution).unwrap();
+        let mut hard_violations =
+            count_hard_violations(&state.solution_of(state.assignment()), &problem) as i64;
+        let num_units = problem.units.len();
+        for _ in 0..200 {
+            let change = Move::unit(rng.gen_range(0..num_units), rng.gen_range(0..4))
+                .and(Move::unit(rng.gen_range(0..num_units), rng.gen_range(0..4)));
+            let (inverse, delta) = state.apply(&change);
+            let expected = objective(&state.solution_of(state.assignment()), &problem).unwrap();
+            assert!((state.score() - expected).abs() < 1e-6);
+            hard_violations += delta.hard_violations;
+            assert_eq!(
+                hard_violations,
+                count_hard_violations(&state.solution_of(state.assignment()), &problem) as i64
+            );
+            let mut isolated = state.isolated().to_vec();
+            isolated.sort_unstable();
+            let expected: Vec<usize> = (0..problem.preferences.students.len())
+                .filter(|&i| is_isolated(&state.layout.wishes[i], state.assignment()))
+                .collect();
+            assert_eq!(isolated, expected);
+            if rng.gen_bool(0.5) {
+                state.undo(&inverse, delta);
+                hard_violations -= delta.hard_violations;
+                let expected = objective(&state.solution_of(state.assignment()), &problem).unwrap();
+                assert!((state.score() - expected).abs() < 1e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    evaluation::{Delta, Move, State},
+    problem::{Problem, SizeBounds},
+};
+use crate::solver::{GroupId, Solution};
+
+/// How many candidate moves to try before giving up on finding one that does not break a hard
+/// constraint.
+const MAX_MOVE_ATTEMPTS: usize = 100;
+
+/// How often to try moving an isolated student next to one of their wishes instead of making a
+/// random move.
+const REPAIR_PROBABILITY: f64 = 0.25;
+
+/// How many random units to try when moving a unit between groups within their bounds.
+const UNIT_SAMPLES: usize = 8;
+
+/// How many random sets of partners to consider when swapping an isolated student towards a wish.
+const PARTNER_SAMPLES: usize = 8;
+
+/// Assigns the units to random groups, respecting pins and filling every group to its minimum
+/// size before any group goes beyond it. Fails if the pins leave a unit no group at all.
+pub fn random_solution(problem: &Problem, rng: &mut impl Rng) -> anyhow::Result<Solution> {
+    let units = &problem.units;
+    let allowed: Vec<Vec<usize>> = (0..units.len())
+        .map(|unit| {
+            (0..problem.num_groups)
+                .filter(|&group| problem.allows_unit(unit, &GroupId(group)))
+                .collect()
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..units.len()).collect();
+    order.shuffle(rng);
+    // place the most restricted and the largest units while their groups still have room
+    order.sort_by_key(|&unit| {
+        (
+            allowed[unit].len(),
+            std::cmp::Reverse(units.members(unit).len()),
+        )
+    });
+
+    let mut group_sizes = vec![0; problem.num_groups];
+    let mut solution = Solution::new();
+    for unit in order {
+        let size = units.members(unit).len();
+        let fits: Vec<usize> = allowed[unit]
+            .iter()
+            .copied()
+            .filter(|&group| group_sizes[group] + size <= problem.group_sizes[group].max)
+            .collect();
+        let below_min: Vec<usize> = fits
+            .iter()
+            .copied()
+            .filter(|&group| group_sizes[group] < problem.group_sizes[group].min)
+            .collect();
+        let Some(candidates) = [below_min, fits, allowed[unit].clone()]
+            .into_iter()
+            .find(|groups| !groups.is_empty())
+        else {
+            let students: Vec<String> = units
+                .members(unit)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            return Err(anyhow::anyhow!(
+                "Students {} must stay together but are not allowed in any group",
+                students.join(", ")
+            ));
+        };
+        let group = candidates[rng.gen_range(0..candidates.len())];
+        group_sizes[group] += size;
+        place_unit(&mut solution, problem, unit, &GroupId(group));
+    }
+    Ok(solution)
+}
+
+/// Makes a move from the current solution and returns the move that undoes it, with the change
+/// in score. Moves that would increase the number of violated hard constraints are taken back,
+/// and no move is made if no acceptable one is found.
+pub fn make_move(
+    state: &mut State,
+    problem: &Problem,
+    rng: &mut impl Rng,
+) -> anyhow::Result<(Move, Delta)> {
+    if !state.has_hard_constraints() {
+        let change = propose_move(state, problem, rng)?;
+        return Ok(state.apply(&change));
+    }
+    for _ in 0..MAX_MOVE_ATTEMPTS {
+        let change = propose_move(state, problem, rng)?;
+        let (inverse, delta) = state.apply(&change);
+        if delta.hard_violations <= 0 {
+            return Ok((inverse, delta));
+        }
+        state.undo(&inverse, delta);
+    }
+    Ok((Move::default(), Delta::default()))
+}
+
+/// Proposes a move from the current solution, whatever it does to the hard constraints.
+pub fn propose_move(
+    state: &mut State,
+    problem: &Problem,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Move> {
+    let num_groups = problem.num_groups;
+    if num_groups == 1 {
+        return Ok(Move::default());
+    }
+
+    if num_groups == 0 {
+        return Err(anyhow::anyhow!("num_groups must be greater than 0"));
+    }
+    // if group sizes are within bounds, rearrange students without breaking the bounds
+    if within_bounds(state.group_sizes(), &problem.group_sizes) {
+        if problem.preferences.guarantee.is_some() && rng.gen_bool(REPAIR_PROBABILITY) {
+            if let Some(change) = join_isolated_student(state, rng) {
+                return Ok(change);
+            }
+        }
+        if rng.gen_bool(0.5) {
+            if let Some(change) = move_within_bounds(state, problem, rng) {
+                return Ok(change);
+            }
+        }
+        return Ok(swap_students_between_groups(state, problem, rng));
+    }
+    Ok(move_from_large_group(state, problem, rng))
+}
+
+/// Returns true if every group size lies within the bounds for that group.
+pub fn within_bounds(group_sizes: &[usize], bounds: &[SizeBounds]) -> bool {
+    group_sizes
+        .iter()
+        .zip(bounds)
+        .all(|(&size, bounds)| bounds.contains(size))
+}
+
+/// Swaps a random unit of one group with units of the same total size from another group.
+fn swap_students_between_groups(state: &State, problem: &Problem, rng: &mut impl Rng) -> Move {
+    let num_groups = problem.num_groups;
+
+    // Randomly select two different groups
+    let group1 = rng.gen_range(0..num_groups);
+    let mut group2 = rng.gen_range(0..num_groups);
+    while group1 == group2 {
+        group2 = rng.gen_range(0..num_groups);
+    }
+
+    // Only units allowed in the other group can be swapped
+    let units_in_group1: Vec<usize> = state
+        .units_in(group1)
+        .iter()
+        .copied()
+        .filter(|&unit| state.allows(unit, group2))
+        .collect();
+    if units_in_group1.is_empty() {
+        return Move::default();
+    }
+    let unit_from_group1 = units_in_group1[rng.gen_range(0..units_in_group1.len())];
+    let size = state.unit_size(unit_from_group1);
+
+    // Ensure the other group has units to swap with, then swap the units between the two groups
+    units_of_total_size(state, group2, group1, size, None, rng)
+        .map(|units_from_group2| swap_units(unit_from_group1, group2, &units_from_group2, group1))
+        .unwrap_or_default()
+}
+
+/// Brings a random isolated student together with one of their wishes, either by swapping the
+/// student's unit into the wish's group or the wish's unit into the student's group. Of the
+/// possible swaps, the one that lowers the objective the most is made.
+fn join_isolated_student(state: &mut State, rng: &mut impl Rng) -> Option<Move> {
+    let &preference = state.isolated().choose(rng)?;
+    let (student, wishes) = state.wishes(preference);
+    let wish = state.unit_of(wishes[rng.gen_range(0..wishes.len())]);
+    let student = state.unit_of(student);
+
+    let mut swaps: Vec<Move> = vec![];
+    for (mover, stayer) in [(student, wish), (wish, student)] {
+        let from_group = state.unit_group(mover);
+        let to_group = state.unit_group(stayer);
+        if !state.allows(mover, to_group) {
+            continue;
+        }
+        let size = state.unit_size(mover);
+        for _ in 0..PARTNER_SAMPLES {
+            if let Some(partners) =
+                units_of_total_size(state, to_group, from_group, size, Some(stayer), rng)
+            {
+                swaps.push(swap_units(mover, to_group, &partners, from_group));
+            }
+        }
+    }
+    swaps
+        .into_iter()
+        .map(|change| {
+            let (inverse, delta) = state.apply(&change);
+            state.undo(&inverse, delta);
+            (change, delta.score)
+        })
+        .min_by(|(_, first), (_, second)| first.total_cmp(second))
+        .map(|(change, _)| change)
+}
+
+/// Picks random units of the group, other than `excluded` and allowed in `target`, holding
+/// exactly `size` students between them.
+fn units_of_total_size(
+    state: &State,
+    group: usize,
+    target: usize,
+    size: usize,
+    excluded: Option<usize>,
+    rng: &mut impl Rng,
+) -> Option<Vec<usize>> {
+    let mut candidates: Vec<usize> = state
+        .units_in(group)
+        .iter()
+        .copied()
+        .filter(|&unit| Some(unit) != excluded && state.allows(unit, target))
+        .collect();
+    candidates.shuffle(rng);
+    let mut chosen = vec![];
+    let mut remaining = size;
+    for unit in candidates {
+        let unit_size = state.unit_size(unit);
+        if unit_size <= remaining {
+            chosen.push(unit);
+            remaining -= unit_size;
+            if remaining == 0 {
+                return Some(chosen);
+            }
+        }
+    }
+    None
+}
+
+/// Moves a random unit from a group that can shrink by its size to a random group that can grow
+/// by it, without any group leaving its bounds. Only a few units are tried, so that a step does not
+/// grow with the size of the class.
+fn move_within_bounds(state: &State, problem: &Problem, rng: &mut impl Rng) -> Option<Move> {
+    let bounds = &problem.group_sizes;
+    let group_sizes = state.group_sizes();
+    let sources: Vec<usize> = (0..group_sizes.len())
+        .filter(|&group| group_sizes[group] > bounds[group].min)
+        .collect();
+    if sources.is_empty() {
+        return None;
+    }
+    for _ in 0..UNIT_SAMPLES {
+        let source = sources[rng.gen_range(0..sources.len())];
+        let unit = *state.units_in(source).choose(rng)?;
+        let size = state.unit_size(unit);
+        if group_sizes[source] < bounds[source].min + size {
+            continue;
+        }
+        let targets: Vec<usize> = (0..group_sizes.len())
+            .filter(|&target| {
+                target != source
+                    && group_sizes[target] + size <= bounds[target].max
+                    && state.allows(unit, target)
+            })
+            .collect();
+        if let Some(&target) = targets.choose(rng) {
+            return Some(Move::unit(unit, target));
+        }
+    }
+    None
+}
+
+/// Moves a unit out of the group that most needs to lose students, into the group allowed for
+/// that unit that most needs to gain them. Groups over their maximum take precedence as the
+/// source and groups under their minimum take precedence as the target.
+fn move_from_large_group(state: &State, problem: &Problem, rng: &mut impl Rng) -> Move {
+    let bounds = &problem.group_sizes;
+    let group_sizes = state.group_sizes();
+    let surplus = |group: usize| {
+        let size = group_sizes[group] as i64;
+        if size > bounds[group].max as i64 {
+            (1, size - bounds[group].max as i64)
+        } else {
+            (0, size - bounds[group].min as i64)
+        }
+    };
+    let shortfall = |group: usize| {
+        let size = group_sizes[group] as i64;
+        if size < bounds[group].min as i64 {
+            (1, bounds[group].min as i64 - size)
+        } else {
+            (0, bounds[group].max as i64 - size)
+        }
+    };
+
+    let mut sources: Vec<usize> = (0..group_sizes.len()).collect();
+    sources.sort_by_key(|&group| std::cmp::Reverse(surplus(group)));
+    for source in sources {
+        // skip over groups whose units are all pinned
+        let movable: Vec<usize> = state
+            .units_in(source)
+            .iter()
+            .copied()
+            .filter(|&unit| {
+                (0..group_sizes.len()).any(|group| group != source && state.allows(unit, group))
+            })
+            .collect();
+        if movable.is_empty() {
+            continue;
+        }
+        let unit = movable[rng.gen_range(0..movable.len())];
+        let target = (0..group_sizes.len())
+            .filter(|&group| group != source && state.allows(unit, group))
+            .max_by_key(|&group| shortfall(group))
+            .unwrap();
+        return Move::unit(unit, target);
+    }
+    Move::default()
+}
+
+/// Moves the unit into `to` and the partner units into `from`.
+fn swap_units(unit: usize, to: usize, partners: &[usize], from: usize) -> Move {
+    partners
+        .iter()
+        .fold(Move::unit(unit, to), |change, &partner| {
+            change.and(Move::unit(partner, from))
+        })
+}
+
+/// Puts every student of the unit into the group.
+fn place_unit(solution: &mut Solution, problem: &Problem, unit: usize, group: &GroupId) {
+    for student in problem.units.members(unit) {
+        solution.insert(student.clone(), group.clone());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use super::{default_weight, Groups, Solution, StudentId};
+
+fn default_decay() -> f64 {
+    0.5
+}
+
+/// Groupings from previous sessions, most recent first.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct History {
+    pub groupings: Vec<Groups>,
+    /// How much a pairing counts for each session further back: a pair who shared a group in
+    /// the last session costs `weight`, the session before `weight * decay`, and so on.
+    #[serde(default = "default_decay")]
+    pub decay: f64,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+/// The penalty for each pair of students who have shared a group before, ordered by student so
+/// that the penalties are always summed in the same order.
+#[derive(Debug, Clone, Default)]
+pub struct RepeatPenalties {
+    pairs: Vec<((StudentId, StudentId), f64)>,
+}
+
+impl RepeatPenalties {
+    /// Collects the pairs from the history, ignoring students who are not in `students`.
+    pub fn new(history: &History, students: &[StudentId]) -> anyhow::Result<Self> {
+        if !history.decay.is_finite() || history.decay <= 0.0 || history.decay > 1.0 {
+            return Err(anyhow::anyhow!(
+                "History decay must be greater than 0 and at most 1 but was {}",
+                history.decay
+            ));
+        }
+        if !history.weight.is_finite() || history.weight < 0.0 {
+            return Err(anyhow::anyhow!(
+                "Invalid weight {} in history",
+                history.weight
+            ));
+        }
+        let mut pairs: HashMap<(StudentId, StudentId), f64> = HashMap::new();
+        let mut session_weight = history.weight;
+        for grouping in &history.groupings {
+            for group in grouping {
+                let members: Vec<&StudentId> = group
+                    .iter()
+                    .filter(|student| students.contains(student))
+                    .collect();
+                for (i, first) in members.iter().enumerate() {
+                    for second in &members[i + 1..] {
+                        *pairs.entry(ordered_pair(first, second)).or_default() += session_weight;
+                    }
+                }
+            }
+            session_weight *= history.decay;
+        }
+        let mut pairs: Vec<((StudentId, StudentId), f64)> = pairs.into_iter().collect();
+        pairs.sort_by_key(|((first, second), _)| (**first, **second));
+        Ok(RepeatPenalties { pairs })
+    }
+
+    /// Each pair who shared a group before, with the penalty for sharing one again.
+    pub fn pairs(&self) -> &[((StudentId, StudentId), f64)] {
+        &self.pairs
+    }
+
+    /// The total penalty of the pairs sharing a group again in the solution.
+    pub fn penalty(&self, solution: &Solution) -> f64 {
+        self.pairs
+            .iter()
+            .filter(|((first, second), _)| solution[first] == solution[second])
+            .map(|(_, penalty)| penalty)
+            .sum()
+    }
+}
+
+fn ordered_pair(first: &StudentId, second: &StudentId) -> (StudentId, StudentId) {
+    if **first <= **second {
+        (first.clone(), second.clone())
+    } else {
+        (second.clone(), first.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::GroupId;
+
+    use super::*;
+
+    #[test]
+    fn test_repeat_penalty() {
+        let students: Vec<StudentId> = (0..4).map(StudentId).collect();
+        let history = History {
+            groupings: vec![
+                vec![vec![0.into(), 1.into()], vec![2.into(), 3.into()]],
+                vec![vec![0.into(), 2.into()], vec![1.into(), 3.into()]],
+                // student 9 has left the class
+                vec![vec![0.into(), 1.into(), 9.into()], vec![2.into(), 3.into()]],
+            ],
+            decay: 0.5,
+            weight: 2.0,
+        };
+        let penalties = RepeatPenalties::new(&history, &students).unwrap();
+
+        let mut solution = Solution::new();
+        solution.insert(0.into(), GroupId(0));
+        solution.insert(1.into(), GroupId(0));
+        solution.insert(2.into(), GroupId(1));
+        solution.insert(3.into(), GroupId(1));
+        // pairs 0-1 and 2-3 met last session and three sessions ago
+        assert_eq!(penalties.penalty(&solution), 5.0);
+
+        solution.insert(1.into(), GroupId(1));
+        solution.insert(3.into(), GroupId(0));
+        // pairs 0-3 and 1-2 have never met
+        assert_eq!(penalties.penalty(&solution), 0.0);
+    }
+}
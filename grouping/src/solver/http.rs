@@ -0,0 +1,283 @@
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    attributes::{AttributeObjective, Attributes},
+    constraints::{Coverage, SpreadSet},
+    history::History,
+    parameters::{SolverOptions, SolverParameters},
+    preferences::{Preferences, Satisfaction},
+    problem::{sizes_for_group_size, Pin, Problem, RemainderPolicy, SizeBounds},
+    Groups, RelationshipPair, RunStatistics, StudentId, Violation,
+};
+
+/// The size of a single group, either exact or a range.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum GroupSize {
+    Exact(usize),
+    Bounds { min: usize, max: usize },
+}
+
+impl From<GroupSize> for SizeBounds {
+    fn from(group_size: GroupSize) -> Self {
+        match group_size {
+            GroupSize::Exact(size) => SizeBounds::exact(size),
+            GroupSize::Bounds { min, max } => SizeBounds { min, max },
+        }
+    }
+}
+
+/// A student given either as a bare id or with attributes and tags.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum StudentInput {
+    Id(StudentId),
+    Student {
+        id: StudentId,
+        #[serde(default)]
+        attributes: Attributes,
+        /// Labels such as "bilingual" or "certified" for coverage constraints.
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+/// Requests either a number of groups or a target group size, but not both.
+#[derive(serde::Deserialize)]
+pub struct CreateGroupsRequest {
+    #[serde(default)]
+    pub num_groups: Option<usize>,
+    /// Make groups of this many students, computing the number of groups from the class size.
+    #[serde(default)]
+    pub group_size: Option<usize>,
+    /// How to handle leftover students when using `group_size`.
+    #[serde(default)]
+    pub remainder_policy: RemainderPolicy,
+    pub students: Vec<StudentInput>,
+    pub relationship_pairs: Vec<RelationshipPair>,
+    /// Sets of students who should be spread out over the groups.
+    #[serde(default)]
+    pub spread_sets: Vec<SpreadSet>,
+    /// Tags that every group must contain.
+    #[serde(default)]
+    pub coverage: Vec<Coverage>,
+    /// Student attributes to spread evenly across the groups, or to group similar students by.
+    #[serde(default)]
+    pub attribute_objectives: Vec<AttributeObjective>,
+    /// Students fixed to a group, or kept out of some groups.
+    #[serde(default)]
+    pub pins: Vec<Pin>,
+    /// Previous groupings, so students work with new people.
+    #[serde(default)]
+    pub history: Option<History>,
+    /// Classmates each student would like to work with, most wanted first.
+    #[serde(default)]
+    pub preferences: Option<Preferences>,
+    /// Sizes for each group. Groups are made as equal as possible when omitted.
+    #[serde(default)]
+    pub group_sizes: Option<Vec<GroupSize>>,
+    /// Seeds the solver, so the same request always gives the same groups. A random seed is
+    /// used when omitted.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Settings for the solver, such as how long to search.
+    #[serde(default)]
+    pub solver: SolverOptions,
+}
+
+/// How the groups were sized when the request asked for a group size.
+#[derive(Debug, serde::Serialize)]
+pub struct GroupSizing {
+    pub group_size: usize,
+    pub remainder_policy: RemainderPolicy,
+    pub num_groups: usize,
+    pub group_sizes: Vec<usize>,
+}
+
+/// Marks an error as coming from a request that does not describe a valid problem, rather than
+/// from the solver itself.
+#[derive(Debug)]
+pub struct InvalidRequest;
+
+impl std::fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid request")
+    }
+}
+
+impl CreateGroupsRequest {
+    /// Builds the problem the request describes. Requests that do not describe a valid problem
+    /// give an error with [`InvalidRequest`] as its context.
+    pub fn into_problem(self) -> anyhow::Result<(Problem, Option<GroupSizing>)> {
+        self.into_problem_unchecked().context(InvalidRequest)
+    }
+
+    fn into_problem_unchecked(self) -> anyhow::Result<(Problem, Option<GroupSizing>)> {
+        let mut students = Vec::with_capacity(self.students.len());
+        let mut attributes = HashMap::new();
+        let mut tags: HashMap<String, Vec<StudentId>> = HashMap::new();
+        for student in self.students {
+            match student {
+                StudentInput::Id(id) => students.push(id),
+                StudentInput::Student {
+                    id,
+                    attributes: student_attributes,
+                    tags: student_tags,
+                } => {
+                    students.push(id.clone());
+                    // a tag listed twice still counts the student once
+                    for tag in student_tags.into_iter().collect::<HashSet<_>>() {
+                        tags.entry(tag).or_default().push(id.clone());
+                    }
+                    attributes.insert(id, student_attributes);
+                }
+            }
+        }
+
+        let mut known = HashSet::new();
+        if let Some(student) = students.iter().find(|student| !known.insert(*student)) {
+            return Err(anyhow::anyhow!("Student {} is listed twice", student));
+        }
+        if let Some(student) = self
+            .relationship_pairs
+            .iter()
+            .flat_map(|pair| [&pair.first_student_id, &pair.second_student_id])
+            .find(|student| !known.contains(student))
+        {
+            return Err(anyhow::anyhow!(
+                "Relationship pair given for unknown student {}",
+                student
+            ));
+        }
+
+        let (problem, sizing) = match (self.num_groups, self.group_size) {
+            (Some(num_groups), None) => {
+                let problem = Problem::new(students, num_groups, self.relationship_pairs);
+                let problem = match self.group_sizes {
+                    Some(group_sizes) => problem.with_group_sizes(
+                        group_sizes.into_iter().map(SizeBounds::from).collect(),
+                    )?,
+                    None => problem,
+                };
+                (problem, None)
+            }
+            (None, Some(group_size)) => {
+                if self.group_sizes.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "group_sizes cannot be combined with group_size"
+                    ));
+                }
+                let group_sizes =
+                    sizes_for_group_size(students.len(), group_size, self.remainder_policy)?;
+                let problem = Problem::new(students, group_sizes.len(), self.relationship_pairs)
+                    .with_group_sizes(
+                        group_sizes.iter().copied().map(SizeBounds::exact).collect(),
+                    )?;
+                let sizing = GroupSizing {
+                    group_size,
+                    remainder_policy: self.remainder_policy,
+                    num_groups: group_sizes.len(),
+                    group_sizes,
+                };
+                (problem, Some(sizing))
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Exactly one of num_groups and group_size must be given"
+                ))
+            }
+        };
+        let problem = problem
+            .with_attributes(&attributes, &self.attribute_objectives)?
+            .with_pins(&self.pins)?
+            .with_spread_sets(self.spread_sets)?
+            .with_coverage(tags, self.coverage)?;
+        let problem = match &self.history {
+            Some(history) => problem.with_history(history)?,
+            None => problem,
+        };
+        let problem = match self.preferences {
+            Some(preferences) => problem.with_preferences(preferences)?,
+            None => problem,
+        };
+        Ok((problem, sizing))
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct CreateGroupsResponse {
+    pub groups: Groups,
+    pub violations: Vec<Violation>,
+    pub objective: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sizing: Option<GroupSizing>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub satisfaction: Vec<Satisfaction>,
+    /// The seed the solver used, to repeat the grouping later.
+    pub seed: u64,
+    /// The solver settings used, with defaults filled in.
+    pub parameters: SolverParameters,
+    pub statistics: RunStatistics,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::RelationshipKind;
+
+    use super::*;
+
+    fn request(num_groups: Option<usize>, group_size: Option<usize>) -> CreateGroupsRequest {
+        CreateGroupsRequest {
+            num_groups,
+            group_size,
+            remainder_policy: RemainderPolicy::default(),
+            students: (0..6).map(|id| StudentInput::Id(id.into())).collect(),
+            relationship_pairs: vec![],
+            spread_sets: vec![],
+            coverage: vec![],
+            attribute_objectives: vec![],
+            pins: vec![],
+            history: None,
+            preferences: None,
+            group_sizes: None,
+            seed: None,
+            solver: SolverOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        assert!(request(Some(2), None).into_problem().is_ok());
+        let error = request(Some(2), Some(3)).into_problem().unwrap_err();
+        assert!(error.downcast_ref::<InvalidRequest>().is_some());
+
+        let mut unknown_partner = request(Some(2), None);
+        unknown_partner.relationship_pairs = vec![RelationshipPair::new(
+            0.into(),
+            99.into(),
+            RelationshipKind::Apart,
+            1.0,
+            true,
+        )];
+        let error = unknown_partner.into_problem().unwrap_err();
+        assert!(error.downcast_ref::<InvalidRequest>().is_some());
+
+        let mut listed_twice = request(Some(2), None);
+        listed_twice.students.push(StudentInput::Id(0.into()));
+        let error = listed_twice.into_problem().unwrap_err();
+        assert!(error.downcast_ref::<InvalidRequest>().is_some());
+    }
+
+    #[test]
+    fn test_repeated_tag() {
+        let mut repeated_tag = request(Some(2), None);
+        repeated_tag.students[0] = StudentInput::Student {
+            id: 0.into(),
+            attributes: Attributes::default(),
+            tags: vec!["x".to_string(), "x".to_string()],
+        };
+        let (problem, _) = repeated_tag.into_problem().unwrap();
+        assert_eq!(problem.tagged_students("x"), [0.into()]);
+    }
+}
@@ -0,0 +1,736 @@
+pub mod analysis;
+pub mod attributes;
+pub mod constraints;
+pub mod diagnosis;
+pub(crate) mod evaluation;
+pub(crate) mod group_move;
+pub mod history;
+pub mod http;
+pub(crate) mod objective;
+pub mod parameters;
+pub mod preferences;
+pub mod problem;
+pub mod units;
+
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+use self::{
+    analysis::get_violations,
+    constraints::{Coverage, SpreadSet},
+    objective::{lower_bound, objective},
+    parameters::SolverParameters,
+    preferences::{Guarantee, Satisfaction},
+};
+
+pub use self::problem::Problem;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StudentId(usize);
+impl std::fmt::Display for StudentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl Deref for StudentId {
+    type Target = usize;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl DerefMut for StudentId {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+impl From<usize> for StudentId {
+    fn from(id: usize) -> Self {
+        StudentId(id)
+    }
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct GroupId(usize);
+impl std::fmt::Display for GroupId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl Deref for GroupId {
+    type Target = usize;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl DerefMut for GroupId {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+impl From<usize> for GroupId {
+    fn from(id: usize) -> Self {
+        GroupId(id)
+    }
+}
+
+/// Whether the two students of a [`RelationshipPair`] should be kept apart or together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipKind {
+    #[default]
+    Apart,
+    Together,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RelationshipPair {
+    pub(crate) first_student_id: StudentId,
+    pub(crate) second_student_id: StudentId,
+    #[serde(default)]
+    pub(crate) kind: RelationshipKind,
+    /// Scales the penalty for violating this pair, so more serious conflicts cost more.
+    #[serde(default = "default_weight")]
+    pub(crate) weight: f64,
+    /// Hard pairs must never be violated; the solver fails rather than return a grouping that
+    /// breaks one.
+    #[serde(default)]
+    pub(crate) hard: bool,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl RelationshipPair {
+    pub fn new(
+        first_student_id: StudentId,
+        second_student_id: StudentId,
+        kind: RelationshipKind,
+        weight: f64,
+        hard: bool,
+    ) -> Self {
+        RelationshipPair {
+            first_student_id,
+            second_student_id,
+            kind,
+            weight,
+            hard,
+        }
+    }
+
+    pub fn students(&self) -> (&StudentId, &StudentId) {
+        (&self.first_student_id, &self.second_student_id)
+    }
+
+    pub fn kind(&self) -> RelationshipKind {
+        self.kind
+    }
+
+    pub fn is_hard(&self) -> bool {
+        self.hard
+    }
+
+    /// Returns true if the pair is violated by the given group assignment of its two students.
+    pub fn is_violated(&self, first_group: &GroupId, second_group: &GroupId) -> bool {
+        match self.kind {
+            RelationshipKind::Apart => first_group == second_group,
+            RelationshipKind::Together => first_group != second_group,
+        }
+    }
+}
+
+pub type Solution = HashMap<StudentId, GroupId>;
+
+pub type Groups = Vec<Vec<StudentId>>;
+
+/// A constraint broken by a grouping. In a response each violation carries a `type` field naming
+/// its kind (`pair`, `spread_set`, `coverage` or `isolated`) next to the fields of that kind, so
+/// clients should check `type` before reading the other fields.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Violation {
+    /// A relationship pair that was not kept. These fields are the ones every violation had
+    /// before there were other kinds.
+    Pair {
+        relationship_pair: RelationshipPair,
+        group: GroupId,
+        weight: f64,
+        /// The group of the second student when a "together" pair was split up.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        second_group: Option<GroupId>,
+    },
+    /// Too many members of a spread set in one group.
+    SpreadSet {
+        spread_set: SpreadSet,
+        group: GroupId,
+        /// The members of the set in the group.
+        students: Vec<StudentId>,
+        weight: f64,
+    },
+    /// Too few students with a tag in one group.
+    Coverage {
+        coverage: Coverage,
+        group: GroupId,
+        /// The number of tagged students in the group.
+        found: usize,
+        weight: f64,
+    },
+    /// A student who shares a group with none of their wishes.
+    Isolated {
+        student_id: StudentId,
+        group: GroupId,
+        wishes: Vec<StudentId>,
+        guarantee: Guarantee,
+        weight: f64,
+    },
+}
+
+impl Violation {
+    pub fn weight(&self) -> f64 {
+        match self {
+            Violation::Pair { weight, .. }
+            | Violation::SpreadSet { weight, .. }
+            | Violation::Coverage { weight, .. }
+            | Violation::Isolated { weight, .. } => *weight,
+        }
+    }
+
+    /// Returns true if the violated constraint is a hard constraint.
+    pub fn is_hard(&self) -> bool {
+        match self {
+            Violation::Pair {
+                relationship_pair, ..
+            } => relationship_pair.hard,
+            Violation::SpreadSet { spread_set, .. } => spread_set.hard,
+            Violation::Coverage { coverage, .. } => coverage.hard,
+            Violation::Isolated { guarantee, .. } => guarantee.hard,
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Violation::Pair {
+                relationship_pair: pair,
+                group,
+                second_group: Some(second_group),
+                ..
+            } => write!(
+                f,
+                "Students {} and {} split between groups {} and {}",
+                pair.first_student_id, pair.second_student_id, group, second_group
+            ),
+            Violation::Pair {
+                relationship_pair: pair,
+                group,
+                second_group: None,
+                ..
+            } => write!(
+                f,
+                "Students {} and {} in group {}",
+                pair.first_student_id, pair.second_student_id, group
+            ),
+            Violation::SpreadSet {
+                spread_set,
+                group,
+                students,
+                ..
+            } => {
+                let students: Vec<String> = students.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "Students {} in group {} but at most {} allowed",
+                    students.join(", "),
+                    group,
+                    spread_set.max_per_group
+                )
+            }
+            Violation::Coverage {
+                coverage,
+                group,
+                found,
+                ..
+            } => write!(
+                f,
+                "Group {} has {} students tagged {} but needs {}",
+                group, found, coverage.tag, coverage.min_per_group
+            ),
+            Violation::Isolated {
+                student_id, group, ..
+            } => write!(
+                f,
+                "Student {} in group {} with none of their wishes",
+                student_id, group
+            ),
+        }
+    }
+}
+
+/// A way of dividing the students of a [`Problem`] into groups. Every solver reads the same
+/// problem and reports its grouping in the same form, so that solvers can be swapped for one
+/// another and compared.
+pub trait Solver: Send + Sync {
+    /// Makes one run, keeping to the limit where the solver can. Fails with [`Infeasible`] if
+    /// the grouping found breaks a hard constraint, or with [`Exhausted`] if the run found no
+    /// grouping at all.
+    fn solve(
+        &self,
+        problem: &Problem,
+        limit: RunLimit,
+        rng: &mut dyn RngCore,
+    ) -> anyhow::Result<Report>;
+
+    /// Roughly how many moves one step of a run scores on the problem, so that step limits of
+    /// different solvers can be held to the same amount of work.
+    fn moves_per_step(&self, _problem: &Problem) -> u64 {
+        1
+    }
+}
+
+/// Returned when the solver cannot find a grouping that satisfies every hard constraint.
+#[derive(Debug, serde::Serialize)]
+pub struct Infeasible {
+    /// The hard constraints still violated by the best grouping found.
+    pub violations: Vec<Violation>,
+}
+
+impl std::fmt::Display for Infeasible {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "No grouping satisfies all hard constraints")?;
+        for violation in &self.violations {
+            write!(f, "; {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Infeasible {}
+
+/// Returned when a run reaches its limit before finding any grouping, so it cannot tell whether
+/// one exists.
+#[derive(Debug)]
+pub struct Exhausted;
+
+impl std::fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "No grouping was found within the limit; allow more steps or time"
+        )
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+/// A grouping found by any of the solvers, with the constraints it violates.
+pub struct Report {
+    pub groups: Groups,
+    pub violations: Vec<Violation>,
+    pub objective: f64,
+    /// Which partner wishes each student got.
+    pub satisfaction: Vec<Satisfaction>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Objective: {}", self.objective)?;
+        writeln!(f, "Violations:")?;
+        for violation in &self.violations {
+            writeln!(f, "{}", violation)?;
+        }
+        writeln!(f, "Groups:")?;
+        for (i, group) in self.groups.iter().enumerate() {
+            writeln!(f, "Group {}: {:?}", i, group)?;
+        }
+        Ok(())
+    }
+}
+
+/// When a run ends. With a time limit the schedule follows the clock, and any step
+/// limit only caps the run; otherwise the schedule follows the steps.
+#[derive(Debug, Clone, Copy)]
+pub struct RunLimit {
+    pub steps: Option<i32>,
+    pub time: Option<Duration>,
+}
+
+impl RunLimit {
+    pub fn steps(steps: i32) -> Self {
+        RunLimit {
+            steps: Some(steps),
+            time: None,
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        RunLimit {
+            steps: None,
+            time: Some(time),
+        }
+    }
+
+    /// How much of the run is done, or `None` once it is over.
+    pub(crate) fn progress(&self, step: i32, start: Instant) -> Option<f64> {
+        if self.steps.is_some_and(|steps| step >= steps) {
+            return None;
+        }
+        let progress = match (self.time, self.steps) {
+            (Some(time), _) => start.elapsed().as_secs_f64() / time.as_secs_f64(),
+            (None, Some(steps)) => step as f64 / steps as f64,
+            (None, None) => return None,
+        };
+        (progress < 1.0).then_some(progress)
+    }
+}
+
+/// How close to the lower bound a score must come to count as reaching it, allowing for rounding.
+pub(crate) const SCORE_TOLERANCE: f64 = 1e-9;
+
+/// The score at which a run can stop, since no grouping can do better.
+pub(crate) fn good_enough_score(problem: &Problem) -> f64 {
+    lower_bound(problem) + SCORE_TOLERANCE
+}
+
+/// Reports on the best grouping of a run, failing if it breaks a hard constraint.
+pub(crate) fn finish(problem: &Problem, best_solution: &Solution) -> anyhow::Result<Report> {
+    // the running score gathers rounding errors, so score the best grouping afresh
+    let best_score = objective(best_solution, problem)?;
+    let violations = get_violations(best_solution, problem);
+    if violations.iter().any(Violation::is_hard) {
+        return Err(Infeasible {
+            violations: violations.into_iter().filter(Violation::is_hard).collect(),
+        }
+        .into());
+    }
+    let result = Report {
+        groups: create_group_list(best_solution, problem),
+        violations,
+        objective: best_score,
+        satisfaction: problem.preferences.satisfaction(best_solution),
+    };
+    debug!("Result: {}", result);
+    Ok(result)
+}
+
+/// Lists the members of each group in the order the students were given.
+fn create_group_list(solution: &Solution, problem: &Problem) -> Vec<Vec<StudentId>> {
+    let mut groups: Vec<Vec<StudentId>> = vec![vec![]; problem.num_groups];
+    for student in &problem.students {
+        groups[*solution[student]].push(student.clone());
+    }
+    groups
+}
+
+/// How the runs of [`solve`] went.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RunStatistics {
+    /// The runs made, fewer than asked for if one reached the lowest possible objective or the
+    /// time ran out.
+    pub runs: usize,
+    pub threads: usize,
+    /// The runs whose grouping satisfies every hard constraint.
+    pub feasible_runs: usize,
+    /// The objective of each feasible run, in the order the runs were started.
+    pub objectives: Vec<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_objective: Option<f64>,
+    pub elapsed_ms: u64,
+}
+
+/// Runs the chosen algorithm `restarts` times, `threads` runs at a time, and returns the best
+/// grouping found. Each run is seeded from `rng` in turn, so without a time budget the same seed
+/// gives the same result whatever the number of threads, unless a run reaches the lowest possible
+/// objective: no more runs are started after that. A time budget is shared out over the runs
+/// each thread has left.
+pub fn solve(
+    problem: &Problem,
+    parameters: &SolverParameters,
+    rng: &mut impl Rng,
+) -> anyhow::Result<(Report, RunStatistics)> {
+    let start = Instant::now();
+    let deadline = parameters
+        .time_budget_ms
+        .map(|budget| start + Duration::from_millis(budget));
+    let lower_bound = good_enough_score(problem);
+    let seeds: Vec<u64> = (0..parameters.restarts).map(|_| rng.gen()).collect();
+    let threads = parameters.threads.min(parameters.restarts);
+    let next_run = AtomicUsize::new(0);
+    let finished = AtomicBool::new(false);
+    let solver = parameters.solver();
+
+    let run_all = || {
+        let mut results = vec![];
+        loop {
+            let run = next_run.fetch_add(1, Ordering::Relaxed);
+            if run >= seeds.len() || finished.load(Ordering::Relaxed) {
+                break;
+            }
+            let limit = RunLimit {
+                steps: parameters.steps,
+                time: deadline.map(|deadline| {
+                    let runs_left = (seeds.len() - run).div_ceil(threads) as u32;
+                    deadline.saturating_duration_since(Instant::now()) / runs_left
+                }),
+            };
+            if !results.is_empty() && limit.time.is_some_and(|time| time.is_zero()) {
+                break;
+            }
+            let mut rng = StdRng::seed_from_u64(seeds[run]);
+            let result = solver.solve(problem, limit, &mut rng);
+            let stop = match &result {
+                Ok(result) => result.objective <= lower_bound,
+                // only running out of luck is worth another try
+                Err(error) => !is_unlucky(error),
+            };
+            if stop {
+                finished.store(true, Ordering::Relaxed);
+            }
+            results.push((run, result));
+        }
+        results
+    };
+    let mut results: Vec<(usize, anyhow::Result<Report>)> = if threads == 1 {
+        run_all()
+    } else {
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|_| scope.spawn(run_all)).collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("solver run panicked"))
+                .collect()
+        })
+    };
+    results.sort_by_key(|(run, _)| *run);
+
+    let objectives: Vec<f64> = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok().map(|result| result.objective))
+        .collect();
+    let statistics = RunStatistics {
+        runs: results.len(),
+        threads,
+        feasible_runs: objectives.len(),
+        mean_objective: (!objectives.is_empty())
+            .then(|| objectives.iter().sum::<f64>() / objectives.len() as f64),
+        objectives,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    };
+    debug!("Run statistics: {:?}", statistics);
+
+    let mut best: Option<Report> = None;
+    let mut unlucky = None;
+    for (_, result) in results {
+        match result {
+            Ok(result) => {
+                if best
+                    .as_ref()
+                    .is_none_or(|best| result.objective < best.objective)
+                {
+                    best = Some(result);
+                }
+            }
+            Err(error) if is_unlucky(&error) => {
+                unlucky.get_or_insert(error);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    match best {
+        Some(best) => Ok((best, statistics)),
+        None => Err(unlucky.expect("at least one run is made")),
+    }
+}
+
+/// Returns true if the error only says that a run found no good grouping, which another run may
+/// still find.
+fn is_unlucky(error: &anyhow::Error) -> bool {
+    error.is::<Infeasible>() || error.is::<Exhausted>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        attributes::{AttributeMode, AttributeObjective, AttributeValue},
+        parameters::{Algorithm, SolverOptions},
+        problem::Pin,
+        *,
+    };
+
+    #[test]
+    fn test_every_solver() {
+        let students: Vec<StudentId> = (0..12).map(StudentId::from).collect();
+        let pairs = vec![
+            RelationshipPair::new(0.into(), 1.into(), RelationshipKind::Apart, 1.0, true),
+            RelationshipPair::new(2.into(), 3.into(), RelationshipKind::Together, 1.0, true),
+            RelationshipPair::new(4.into(), 5.into(), RelationshipKind::Apart, 1.0, false),
+        ];
+        // student 6 may only be in the last group
+        let pins = [Pin {
+            student_id: 6.into(),
+            group: Some(2.into()),
+            forbidden_groups: vec![],
+        }];
+        let problem = Problem::new(students, 3, pairs).with_pins(&pins).unwrap();
+        for algorithm in [
+            Algorithm::Annealing,
+            Algorithm::Tabu { tenure: 5 },
+            Algorithm::Genetic { population: 10 },
+            Algorithm::Backtracking,
+        ] {
+            let options = SolverOptions {
+                algorithm,
+                ..Default::default()
+            };
+            let parameters = options.resolve(&problem).unwrap();
+            let solver = parameters.solver();
+            let limit = RunLimit {
+                steps: parameters.steps,
+                time: None,
+            };
+            let report = solver
+                .solve(&problem, limit, &mut StdRng::seed_from_u64(1))
+                .unwrap();
+            assert!(report.violations.is_empty(), "{:?}", algorithm);
+            assert_eq!(
+                report.groups.iter().map(Vec::len).collect::<Vec<_>>(),
+                [4, 4, 4]
+            );
+            assert!(report.groups[2].contains(&6.into()), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_run_limit_progress() {
+        let now = Instant::now();
+        let by_steps = RunLimit::steps(10);
+        assert_eq!(by_steps.progress(5, now), Some(0.5));
+        assert_eq!(by_steps.progress(10, now), None);
+
+        // the clock drives the schedule, whatever the step
+        let by_time = RunLimit::time(Duration::from_millis(200));
+        let progress = by_time
+            .progress(1_000_000, now - Duration::from_millis(150))
+            .unwrap();
+        assert!((0.75..0.9).contains(&progress));
+        assert_eq!(by_time.progress(0, now - Duration::from_millis(200)), None);
+
+        // a step limit still caps a timed run
+        let capped = RunLimit {
+            steps: Some(10),
+            time: Some(Duration::from_secs(60)),
+        };
+        assert!(capped
+            .progress(9, now)
+            .is_some_and(|progress| progress < 0.01));
+        assert_eq!(capped.progress(10, now), None);
+    }
+
+    #[test]
+    fn test_random_solution_unit_without_group() {
+        let students: Vec<StudentId> = (0..8).map(StudentId).collect();
+        let together =
+            RelationshipPair::new(0.into(), 1.into(), RelationshipKind::Together, 1.0, true);
+        let pin = |student: usize, group: usize| Pin {
+            student_id: student.into(),
+            group: Some(group.into()),
+            forbidden_groups: vec![],
+        };
+        let problem = Problem::new(students, 2, vec![together])
+            .with_pins(&[pin(0, 0), pin(1, 1)])
+            .unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(group_move::random_solution(&problem, &mut rng).is_err());
+    }
+
+    /// A class whose uneven levels no grouping can balance perfectly, so that no run stops
+    /// early.
+    fn uneven_problem() -> Problem {
+        let students: Vec<StudentId> = (0..21).map(StudentId).collect();
+        let attributes = students
+            .iter()
+            .map(|student| {
+                let value = AttributeValue::Numeric((**student as f64).sqrt());
+                (
+                    student.clone(),
+                    HashMap::from([("level".to_string(), value)]),
+                )
+            })
+            .collect();
+        let objectives = [AttributeObjective {
+            attribute: "level".to_string(),
+            weight: 1.0,
+            mode: AttributeMode::Balance,
+        }];
+        Problem::new(students, 4, vec![])
+            .with_attributes(&attributes, &objectives)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_solve_with_restarts_and_budget() {
+        let problem = uneven_problem();
+        let options = SolverOptions {
+            restarts: Some(3),
+            threads: Some(1),
+            time_budget_ms: Some(200),
+            ..Default::default()
+        };
+        let parameters = options.resolve(&problem).unwrap();
+        assert_eq!(parameters.steps, None);
+        let (result, statistics) = solve(&problem, &parameters, &mut rand::thread_rng()).unwrap();
+        // the runs share out the budget and use all of it; a busy machine may take longer
+        assert_eq!(statistics.runs, 3);
+        assert!(statistics.elapsed_ms >= 195);
+        assert!(statistics.elapsed_ms < 10_000);
+        assert_eq!(result.groups.iter().map(Vec::len).sum::<usize>(), 21);
+    }
+
+    #[test]
+    fn test_solve_in_parallel() {
+        let problem = uneven_problem();
+        let solve_with = |threads| {
+            let options = SolverOptions {
+                steps: Some(2000),
+                restarts: Some(6),
+                threads: Some(threads),
+                ..Default::default()
+            };
+            let parameters = options.resolve(&problem).unwrap();
+            solve(&problem, &parameters, &mut StdRng::seed_from_u64(5)).unwrap()
+        };
+        let (sequential, sequential_statistics) = solve_with(1);
+        let (parallel, parallel_statistics) = solve_with(3);
+        assert_eq!(parallel_statistics.threads, 3);
+        assert_eq!(parallel_statistics.runs, 6);
+        assert_eq!(
+            parallel_statistics.objectives,
+            sequential_statistics.objectives
+        );
+        assert_eq!(parallel.groups, sequential.groups);
+    }
+
+    #[test]
+    fn test_solve_stops_at_lower_bound() {
+        let students: Vec<StudentId> = (0..21).map(StudentId).collect();
+        let problem = Problem::new(students, 4, vec![]);
+        let options = SolverOptions {
+            steps: Some(200_000),
+            restarts: Some(100),
+            ..Default::default()
+        };
+        let parameters = options.resolve(&problem).unwrap();
+        let start = Instant::now();
+        let (result, statistics) = solve(&problem, &parameters, &mut rand::thread_rng()).unwrap();
+        // the first grouping with even sizes cannot be improved on
+        assert_eq!(result.objective, 0.0);
+        assert!(statistics.runs < 100);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,243 @@
+use tracing::{error, trace};
+
+use super::{problem::Problem, Solution};
+
+/// Penalty for violating a relationship pair of weight 1.0, for each student over the limit of a
+/// spread set of weight 1.0, for each tagged student a group is missing for coverage of
+/// weight 1.0, or for each isolated student under a guarantee of weight 1.0.
+const VIOLATION_PENALTY: f64 = 2.0;
+/// Penalty for each violation of a hard constraint, large enough to steer the search towards
+/// groupings that satisfy every hard constraint.
+const HARD_VIOLATION_PENALTY: f64 = 100.0;
+
+/// The penalty for each violation of a constraint.
+pub(crate) fn penalty_per_violation(hard: bool, weight: f64) -> f64 {
+    if hard {
+        HARD_VIOLATION_PENALTY
+    } else {
+        VIOLATION_PENALTY * weight
+    }
+}
+
+#[tracing::instrument]
+pub(crate) fn objective(solution: &Solution, problem: &Problem) -> Result<f64, anyhow::Error> {
+    let num_groups = problem.num_groups;
+    let mut num_violations = 0f64;
+    let mut violation_penalty = 0f64;
+    for restriction in &problem.relationship_pairs {
+        if solution.get(&restriction.first_student_id).is_none()
+            || solution.get(&restriction.second_student_id).is_none()
+        {
+            error!(
+                first_student_id = restriction.first_student_id.0,
+                second_student_id = restriction.second_student_id.0,
+                "Invalid student id in relationship pair",
+            );
+            return Err(anyhow::anyhow!("Invalid student id in relationship pair"));
+        }
+        if !restriction.weight.is_finite() || restriction.weight < 0.0 {
+            error!(
+                weight = restriction.weight,
+                "Invalid weight in relationship pair"
+            );
+            return Err(anyhow::anyhow!("Invalid weight in relationship pair"));
+        }
+        if restriction.is_violated(
+            &solution[&restriction.first_student_id],
+            &solution[&restriction.second_student_id],
+        ) {
+            num_violations += 1.0;
+            violation_penalty += penalty_per_violation(restriction.hard, restriction.weight);
+        }
+    }
+
+    let mut group_sizes = vec![0; num_groups];
+    for group in solution.values() {
+        group_sizes[**group] += 1;
+    }
+
+    for spread_set in &problem.spread_sets {
+        let excess = spread_set.excess(solution) as f64;
+        num_violations += excess;
+        violation_penalty += excess * penalty_per_violation(spread_set.hard, spread_set.weight);
+    }
+
+    for coverage in &problem.coverage {
+        let shortfall =
+            coverage.shortfall(solution, problem.tagged_students(&coverage.tag), num_groups) as f64;
+        num_violations += shortfall;
+        violation_penalty += shortfall * penalty_per_violation(coverage.hard, coverage.weight);
+    }
+
+    if let Some(guarantee) = &problem.preferences.guarantee {
+        let isolated = problem.preferences.isolated_students(solution).count() as f64;
+        num_violations += isolated;
+        violation_penalty += isolated * penalty_per_violation(guarantee.hard, guarantee.weight);
+    }
+
+    // squared distance of each group from its size bounds, averaged over the groups
+    let size_penalty = group_sizes
+        .iter()
+        .zip(&problem.group_sizes)
+        .map(|(&size, bounds)| (bounds.distance(size) as f64).powi(2))
+        .sum::<f64>()
+        / num_groups as f64;
+
+    let attribute_penalty = problem
+        .attribute_terms
+        .iter()
+        .map(|term| term.penalty(solution, num_groups))
+        .sum::<f64>();
+
+    let repeat_penalty = problem.repeat_penalties.penalty(solution);
+    let preference_penalty = problem.preferences.penalty(solution);
+
+    let score =
+        size_penalty + violation_penalty + attribute_penalty + repeat_penalty + preference_penalty;
+    trace!(
+        "Objective function: size penalty: {}, violations: {}, attribute penalty: {}, repeat penalty: {}, preference penalty: {}, score: {}",
+        size_penalty,
+        num_violations,
+        attribute_penalty,
+        repeat_penalty,
+        preference_penalty,
+        score
+    );
+    Ok(score)
+}
+
+/// A score no grouping can beat. Every part of the objective is at least zero, and balancing a
+/// category cannot do better than rounding each group's share to whole students.
+pub(crate) fn lower_bound(problem: &Problem) -> f64 {
+    problem
+        .attribute_terms
+        .iter()
+        .map(|term| term.lower_bound(&problem.group_sizes, problem.students.len()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{
+        problem::SizeBounds, GroupId, RelationshipKind, RelationshipPair, StudentId,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_objective_function() {
+        let mut solution = Solution::new();
+        solution.insert(StudentId(1), GroupId(0));
+        solution.insert(StudentId(2), GroupId(0));
+        solution.insert(StudentId(3), GroupId(1));
+        solution.insert(StudentId(4), GroupId(1));
+        solution.insert(StudentId(5), GroupId(2));
+        solution.insert(StudentId(6), GroupId(2));
+
+        let restrictions = vec![
+            RelationshipPair {
+                first_student_id: 1.into(),
+                second_student_id: 2.into(),
+                kind: RelationshipKind::Apart,
+                weight: 1.0,
+                hard: false,
+            },
+            RelationshipPair {
+                first_student_id: 3.into(),
+                second_student_id: 4.into(),
+                kind: RelationshipKind::Apart,
+                weight: 1.0,
+                hard: false,
+            },
+            RelationshipPair {
+                first_student_id: 5.into(),
+                second_student_id: 6.into(),
+                kind: RelationshipKind::Apart,
+                weight: 1.0,
+                hard: false,
+            },
+        ];
+
+        let problem = Problem::new(solution.keys().cloned().collect(), 3, restrictions);
+        let result = objective(&solution, &problem).unwrap();
+        assert_eq!(result, 6.0);
+    }
+
+    #[test]
+    fn test_objective_function_together_pairs() {
+        let mut solution = Solution::new();
+        solution.insert(StudentId(1), GroupId(0));
+        solution.insert(StudentId(2), GroupId(0));
+        solution.insert(StudentId(3), GroupId(1));
+        solution.insert(StudentId(4), GroupId(1));
+
+        let restrictions = vec![
+            RelationshipPair {
+                first_student_id: 1.into(),
+                second_student_id: 2.into(),
+                kind: RelationshipKind::Together,
+                weight: 1.0,
+                hard: false,
+            },
+            RelationshipPair {
+                first_student_id: 2.into(),
+                second_student_id: 3.into(),
+                kind: RelationshipKind::Together,
+                weight: 1.0,
+                hard: false,
+            },
+        ];
+
+        let problem = Problem::new(solution.keys().cloned().collect(), 2, restrictions);
+        let result = objective(&solution, &problem).unwrap();
+        assert_eq!(result, 2.0);
+    }
+
+    #[test]
+    fn test_objective_function_weighted_pairs() {
+        let mut solution = Solution::new();
+        solution.insert(StudentId(1), GroupId(0));
+        solution.insert(StudentId(2), GroupId(0));
+        solution.insert(StudentId(3), GroupId(1));
+        solution.insert(StudentId(4), GroupId(1));
+
+        let restrictions = vec![
+            RelationshipPair {
+                first_student_id: 1.into(),
+                second_student_id: 2.into(),
+                kind: RelationshipKind::Apart,
+                weight: 0.25,
+                hard: false,
+            },
+            RelationshipPair {
+                first_student_id: 3.into(),
+                second_student_id: 4.into(),
+                kind: RelationshipKind::Apart,
+                weight: 5.0,
+                hard: false,
+            },
+        ];
+
+        let problem = Problem::new(solution.keys().cloned().collect(), 2, restrictions);
+        let result = objective(&solution, &problem).unwrap();
+        assert_eq!(result, 10.5);
+    }
+
+    #[test]
+    fn test_objective_function_group_sizes() {
+        let mut solution = Solution::new();
+        for student in 0..5 {
+            solution.insert(StudentId(student), GroupId(0));
+        }
+        solution.insert(StudentId(5), GroupId(1));
+
+        let problem = Problem::new(solution.keys().cloned().collect(), 2, vec![])
+            .with_group_sizes(vec![SizeBounds { min: 3, max: 4 }, SizeBounds::exact(2)])
+            .unwrap();
+        // one student too many in the first group and one too few in the second
+        assert_eq!(objective(&solution, &problem).unwrap(), 1.0);
+
+        solution.insert(StudentId(0), GroupId(1));
+        assert_eq!(objective(&solution, &problem).unwrap(), 0.0);
+    }
+}
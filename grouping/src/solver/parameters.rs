@@ -0,0 +1,371 @@
+use anyhow::Context;
+
+use super::{problem::Problem, Solver};
+use crate::{
+    annealing::{temperature::Schedule, Annealing},
+    backtracking::Backtracking,
+    genetic::GeneticSearch,
+    tabu::TabuSearch,
+};
+
+const DEFAULT_MAX_TEMP: f64 = 10.0;
+const DEFAULT_MIN_TEMP: f64 = 0.1;
+/// Bigger classes need longer runs, so by default each student gets this many steps.
+const DEFAULT_STEPS_PER_STUDENT: i32 = 100;
+const MIN_DEFAULT_STEPS: i32 = 1000;
+/// Each iteration of tabu search looks at every move, so it needs far fewer of them.
+const DEFAULT_TABU_ITERATIONS_PER_STUDENT: i32 = 10;
+const MIN_DEFAULT_TABU_ITERATIONS: i32 = 200;
+/// Each child of the genetic search gets a local search of its own.
+const DEFAULT_GENERATIONS_PER_STUDENT: i32 = 5;
+const MIN_DEFAULT_GENERATIONS: i32 = 200;
+const MAX_TENURE: usize = 10_000;
+const MAX_POPULATION: usize = 500;
+const MAX_STEPS: i32 = 10_000_000;
+const MAX_TEMP: f64 = 1e6;
+/// A fixed number rather than one run per core, so that a seed gives the same groups on any
+/// machine.
+const DEFAULT_RESTARTS: usize = 8;
+const MAX_RESTARTS: usize = 100;
+const MAX_TIME_BUDGET_MS: u64 = 60_000;
+const MAX_THREADS: usize = 64;
+/// Without a time budget, all runs together may score no more moves than this. A step of
+/// annealing scores one move, but a step of tabu or genetic search scores many.
+const MAX_TOTAL_MOVES: u64 = 20_000_000;
+
+fn default_tenure() -> usize {
+    10
+}
+
+fn default_population() -> usize {
+    20
+}
+
+/// The search algorithms that can be chosen in a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Algorithm {
+    #[default]
+    Annealing,
+    /// Makes the best move that is not tabu at each step. A unit moved out of a group may not
+    /// go back to it for `tenure` steps, unless that would give the best grouping yet.
+    Tabu {
+        #[serde(default = "default_tenure")]
+        tenure: usize,
+    },
+    /// Breeds a population of groupings, passing on whole groups from parent to child and
+    /// improving each child by local search.
+    Genetic {
+        #[serde(default = "default_population")]
+        population: usize,
+    },
+    /// Tries every assignment in turn until one keeps all relationship pairs. The step limit
+    /// caps the assignments tried.
+    Backtracking,
+}
+
+/// Solver settings as given in a request. Anything left out gets a default.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct SolverOptions {
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    /// Steps of annealing, iterations of tabu search, children of the genetic search, or
+    /// assignments tried by backtracking.
+    #[serde(default)]
+    pub steps: Option<i32>,
+    #[serde(default)]
+    pub max_temp: Option<f64>,
+    #[serde(default)]
+    pub min_temp: Option<f64>,
+    /// How the temperature falls over each run.
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// How many independent runs to make, keeping the best grouping.
+    #[serde(default)]
+    pub restarts: Option<usize>,
+    /// How many runs to make at the same time. Defaults to the number of CPU cores.
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// Gives the solver this many milliseconds over all runs, and cools by the clock rather than
+    /// by steps. The result then depends on the speed of the machine, so a seed alone does not
+    /// reproduce it.
+    #[serde(default)]
+    pub time_budget_ms: Option<u64>,
+}
+
+/// Marks an error as coming from solver settings that are out of range, rather than from the
+/// solver itself.
+#[derive(Debug)]
+pub struct InvalidParameters;
+
+impl std::fmt::Display for InvalidParameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid solver parameters")
+    }
+}
+
+/// The solver settings actually used.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SolverParameters {
+    pub algorithm: Algorithm,
+    /// Not set when a time budget drives the run instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps: Option<i32>,
+    pub max_temp: f64,
+    pub min_temp: f64,
+    pub schedule: Schedule,
+    pub restarts: usize,
+    pub threads: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_budget_ms: Option<u64>,
+}
+
+impl SolverParameters {
+    /// The solver for the chosen algorithm.
+    pub fn solver(&self) -> Box<dyn Solver> {
+        match self.algorithm {
+            Algorithm::Annealing => Box::new(Annealing {
+                schedule: self.schedule.clone(),
+                max_temp: self.max_temp,
+                min_temp: self.min_temp,
+            }),
+            Algorithm::Tabu { tenure } => Box::new(TabuSearch { tenure }),
+            Algorithm::Genetic { population } => Box::new(GeneticSearch { population }),
+            Algorithm::Backtracking => Box::new(Backtracking),
+        }
+    }
+}
+
+impl SolverOptions {
+    /// Fills in the defaults for the problem and checks every setting is in range. Without a time
+    /// budget, the default number of steps is lowered if the runs would otherwise score too many
+    /// moves. Settings out of range give an error with [`InvalidParameters`] as its context.
+    pub fn resolve(self, problem: &Problem) -> anyhow::Result<SolverParameters> {
+        self.resolve_unchecked(problem).context(InvalidParameters)
+    }
+
+    fn resolve_unchecked(self, problem: &Problem) -> anyhow::Result<SolverParameters> {
+        let (per_student, minimum) = match self.algorithm {
+            Algorithm::Annealing | Algorithm::Backtracking => {
+                (DEFAULT_STEPS_PER_STUDENT, MIN_DEFAULT_STEPS)
+            }
+            Algorithm::Tabu { .. } => (
+                DEFAULT_TABU_ITERATIONS_PER_STUDENT,
+                MIN_DEFAULT_TABU_ITERATIONS,
+            ),
+            Algorithm::Genetic { .. } => (DEFAULT_GENERATIONS_PER_STUDENT, MIN_DEFAULT_GENERATIONS),
+        };
+        let default_steps = i32::try_from(problem.students.len())
+            .unwrap_or(i32::MAX)
+            .saturating_mul(per_student)
+            .clamp(minimum, MAX_STEPS);
+        let threads = self.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map_or(1, usize::from)
+                .min(MAX_THREADS)
+        });
+        let mut parameters = SolverParameters {
+            algorithm: self.algorithm,
+            steps: self.steps,
+            max_temp: self.max_temp.unwrap_or(DEFAULT_MAX_TEMP),
+            min_temp: self.min_temp.unwrap_or(DEFAULT_MIN_TEMP),
+            schedule: self.schedule.unwrap_or_default(),
+            restarts: self.restarts.unwrap_or(DEFAULT_RESTARTS),
+            threads,
+            time_budget_ms: self.time_budget_ms,
+        };
+        let moves_per_step = parameters.solver().moves_per_step(problem).max(1);
+        if parameters.steps.is_none() && parameters.time_budget_ms.is_none() {
+            let affordable = MAX_TOTAL_MOVES / (moves_per_step * parameters.restarts.max(1) as u64);
+            let affordable = i32::try_from(affordable).unwrap_or(i32::MAX).max(1);
+            parameters.steps = Some(default_steps.min(affordable));
+        }
+        if let Some(steps) = parameters
+            .steps
+            .filter(|steps| !(1..=MAX_STEPS).contains(steps))
+        {
+            return Err(anyhow::anyhow!(
+                "steps must be between 1 and {} but was {}",
+                MAX_STEPS,
+                steps
+            ));
+        }
+        if parameters.max_temp > MAX_TEMP {
+            return Err(anyhow::anyhow!(
+                "max_temp must be at most {} but was {}",
+                MAX_TEMP,
+                parameters.max_temp
+            ));
+        }
+        if !(1..=MAX_RESTARTS).contains(&parameters.restarts) {
+            return Err(anyhow::anyhow!(
+                "restarts must be between 1 and {} but was {}",
+                MAX_RESTARTS,
+                parameters.restarts
+            ));
+        }
+        match parameters.algorithm {
+            Algorithm::Tabu { tenure } if !(1..=MAX_TENURE).contains(&tenure) => {
+                return Err(anyhow::anyhow!(
+                    "tenure must be between 1 and {} but was {}",
+                    MAX_TENURE,
+                    tenure
+                ));
+            }
+            Algorithm::Genetic { population } if !(2..=MAX_POPULATION).contains(&population) => {
+                return Err(anyhow::anyhow!(
+                    "population must be between 2 and {} but was {}",
+                    MAX_POPULATION,
+                    population
+                ));
+            }
+            _ => {}
+        }
+        if let (Some(steps), None) = (parameters.steps, parameters.time_budget_ms) {
+            let total = steps as u64 * parameters.restarts as u64 * moves_per_step;
+            if total > MAX_TOTAL_MOVES {
+                return Err(anyhow::anyhow!(
+                    "the runs would score about {} moves but at most {} are allowed; give a time \
+                     budget for longer runs",
+                    total,
+                    MAX_TOTAL_MOVES
+                ));
+            }
+        }
+        if !(1..=MAX_THREADS).contains(&parameters.threads) {
+            return Err(anyhow::anyhow!(
+                "threads must be between 1 and {} but was {}",
+                MAX_THREADS,
+                parameters.threads
+            ));
+        }
+        if let Some(budget) = parameters
+            .time_budget_ms
+            .filter(|budget| !(1..=MAX_TIME_BUDGET_MS).contains(budget))
+        {
+            return Err(anyhow::anyhow!(
+                "time_budget_ms must be between 1 and {} but was {}",
+                MAX_TIME_BUDGET_MS,
+                budget
+            ));
+        }
+        if parameters.algorithm == Algorithm::Backtracking {
+            Backtracking::check_supported(problem)?;
+        }
+        // building the schedule checks the temperatures and the schedule settings
+        parameters
+            .schedule
+            .build(parameters.max_temp, parameters.min_temp)?;
+        Ok(parameters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{constraints::SpreadSet, StudentId};
+
+    use super::*;
+
+    /// A class in groups of about ten.
+    fn class(num_students: usize) -> Problem {
+        let students = (0..num_students).map(StudentId::from).collect();
+        Problem::new(students, (num_students / 10).max(1), vec![])
+    }
+
+    #[test]
+    fn test_resolve_parameters() {
+        let parameters = SolverOptions::default().resolve(&class(300)).unwrap();
+        assert_eq!(parameters.steps, Some(30_000));
+        assert_eq!(parameters.restarts, DEFAULT_RESTARTS);
+        assert_eq!(
+            SolverOptions::default().resolve(&class(4)).unwrap().steps,
+            Some(1000)
+        );
+
+        let no_restarts = SolverOptions {
+            restarts: Some(0),
+            ..Default::default()
+        };
+        assert!(no_restarts.resolve(&class(30)).is_err());
+        let no_threads = SolverOptions {
+            threads: Some(0),
+            ..Default::default()
+        };
+        assert!(no_threads.resolve(&class(30)).is_err());
+        let tabu = SolverOptions {
+            algorithm: Algorithm::Tabu { tenure: 5 },
+            ..Default::default()
+        };
+        assert_eq!(tabu.clone().resolve(&class(30)).unwrap().steps, Some(300));
+        // each iteration looks at every move, so a large class gets fewer of them
+        assert_eq!(tabu.resolve(&class(300)).unwrap().steps, Some(260));
+        let long_tenure = SolverOptions {
+            algorithm: Algorithm::Tabu { tenure: usize::MAX },
+            ..Default::default()
+        };
+        assert!(long_tenure.resolve(&class(300)).is_err());
+        let inverted_temps = SolverOptions {
+            max_temp: Some(1.0),
+            min_temp: Some(2.0),
+            ..Default::default()
+        };
+        assert!(inverted_temps.resolve(&class(30)).is_err());
+
+        let too_long = SolverOptions {
+            steps: Some(MAX_STEPS),
+            restarts: Some(MAX_RESTARTS),
+            ..Default::default()
+        };
+        let error = too_long.clone().resolve(&class(30)).unwrap_err();
+        assert!(error.downcast_ref::<InvalidParameters>().is_some());
+        let budgeted = SolverOptions {
+            time_budget_ms: Some(1000),
+            ..too_long
+        };
+        assert!(budgeted.resolve(&class(30)).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_caps_the_moves_scored() {
+        for algorithm in [
+            Algorithm::Tabu { tenure: 10 },
+            Algorithm::Genetic { population: 20 },
+        ] {
+            let long = SolverOptions {
+                algorithm,
+                steps: Some(MAX_STEPS),
+                ..Default::default()
+            };
+            let error = long.clone().resolve(&class(300)).unwrap_err();
+            assert!(error.downcast_ref::<InvalidParameters>().is_some());
+            let budgeted = SolverOptions {
+                time_budget_ms: Some(1000),
+                ..long
+            };
+            assert!(budgeted.resolve(&class(300)).is_ok());
+            assert!(SolverOptions {
+                algorithm,
+                ..Default::default()
+            }
+            .resolve(&class(300))
+            .is_ok());
+        }
+    }
+    #[test]
+    fn test_resolve_rejects_backtracking_with_hard_spread_sets() {
+        let spread_set = SpreadSet {
+            students: vec![0.into(), 1.into()],
+            max_per_group: 1,
+            weight: 1.0,
+            hard: true,
+        };
+        let problem = class(30).with_spread_sets(vec![spread_set]).unwrap();
+        let backtracking = SolverOptions {
+            algorithm: Algorithm::Backtracking,
+            ..Default::default()
+        };
+        let error = backtracking.resolve(&problem).unwrap_err();
+        assert!(error.downcast_ref::<InvalidParameters>().is_some());
+        assert!(SolverOptions::default().resolve(&problem).is_ok());
+    }
+}
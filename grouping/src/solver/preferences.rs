@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use super::{default_weight, GroupId, Solution, StudentId};
+
+/// Extra penalty, relative to a first wish, for a student who gets none of their wishes.
+const NO_WISH_GRANTED_PENALTY: f64 = 2.0;
+
+/// The classmates a student would like to work with, most wanted first.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Preference {
+    pub student_id: StudentId,
+    pub wishes: Vec<StudentId>,
+}
+
+/// Requires every student with wishes to share a group with at least one of them.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Guarantee {
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub hard: bool,
+}
+
+/// Partner wishes for the class. A wish costs nothing when granted and `weight / rank` when not,
+/// so higher ranked wishes are granted first, and a student with no wish granted costs extra so
+/// that wishes are shared out fairly.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Preferences {
+    pub students: Vec<Preference>,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Turns "nobody is left without any of their wishes" into a constraint.
+    #[serde(default)]
+    pub guarantee: Option<Guarantee>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            students: vec![],
+            weight: default_weight(),
+            guarantee: None,
+        }
+    }
+}
+
+/// Which wishes of a student were granted.
+#[derive(Debug, serde::Serialize)]
+pub struct Satisfaction {
+    pub student_id: StudentId,
+    /// The granted wishes, most wanted first.
+    pub satisfied: Vec<StudentId>,
+    pub num_wishes: usize,
+    /// The rank of the most wanted granted wish, starting at 1.
+    pub best_rank: Option<usize>,
+}
+
+impl Preference {
+    /// Returns true if the student listed wishes but shares a group with none of them.
+    pub fn is_isolated(&self, solution: &Solution) -> bool {
+        let group = &solution[&self.student_id];
+        !self.wishes.is_empty() && self.wishes.iter().all(|wish| solution[wish] != *group)
+    }
+}
+
+impl Preferences {
+    pub fn validate(&self, students: &[StudentId]) -> anyhow::Result<()> {
+        let guarantee_weight = self.guarantee.as_ref().map(|guarantee| guarantee.weight);
+        if let Some(weight) = std::iter::once(self.weight)
+            .chain(guarantee_weight)
+            .find(|weight| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(anyhow::anyhow!("Invalid weight {} in preferences", weight));
+        }
+        let mut seen = HashSet::new();
+        for preference in &self.students {
+            if !seen.insert(&preference.student_id) {
+                return Err(anyhow::anyhow!(
+                    "Preferences given twice for student {}",
+                    preference.student_id
+                ));
+            }
+            if let Some(student) = std::iter::once(&preference.student_id)
+                .chain(&preference.wishes)
+                .find(|student| !students.contains(student))
+            {
+                return Err(anyhow::anyhow!(
+                    "Preferences refer to unknown student {}",
+                    student
+                ));
+            }
+            if preference.wishes.contains(&preference.student_id) {
+                return Err(anyhow::anyhow!(
+                    "Student {} wishes to work with themselves",
+                    preference.student_id
+                ));
+            }
+            let unique: HashSet<&StudentId> = preference.wishes.iter().collect();
+            if unique.len() != preference.wishes.len() {
+                return Err(anyhow::anyhow!(
+                    "Student {} lists the same wish twice",
+                    preference.student_id
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn penalty(&self, solution: &Solution) -> f64 {
+        self.students
+            .iter()
+            .map(|preference| self.student_penalty(preference, solution))
+            .sum()
+    }
+
+    /// The part of the penalty due to one student's wishes.
+    pub fn student_penalty(&self, preference: &Preference, solution: &Solution) -> f64 {
+        let group = &solution[&preference.student_id];
+        self.wish_penalty(
+            preference
+                .wishes
+                .iter()
+                .map(|wish| solution[wish] == *group),
+        )
+    }
+
+    /// The penalty for a student given whether each of their wishes, most wanted first, was
+    /// granted.
+    pub fn wish_penalty(&self, granted: impl IntoIterator<Item = bool>) -> f64 {
+        let mut has_wishes = false;
+        let mut any_granted = false;
+        let mut penalty = 0.0;
+        for (rank, granted) in granted.into_iter().enumerate() {
+            has_wishes = true;
+            if granted {
+                any_granted = true;
+            } else {
+                penalty += 1.0 / (rank + 1) as f64;
+            }
+        }
+        if !has_wishes {
+            return 0.0;
+        }
+        if !any_granted {
+            penalty += NO_WISH_GRANTED_PENALTY;
+        }
+        self.weight * penalty
+    }
+
+    /// The students who listed wishes but share a group with none of them, with their group.
+    pub fn isolated_students<'a>(
+        &'a self,
+        solution: &'a Solution,
+    ) -> impl Iterator<Item = (&'a Preference, &'a GroupId)> + 'a {
+        self.students.iter().filter_map(move |preference| {
+            preference
+                .is_isolated(solution)
+                .then_some((preference, &solution[&preference.student_id]))
+        })
+    }
+
+    pub fn satisfaction(&self, solution: &Solution) -> Vec<Satisfaction> {
+        self.students
+            .iter()
+            .map(|preference| {
+                let group = &solution[&preference.student_id];
+                let satisfied: Vec<StudentId> = preference
+                    .wishes
+                    .iter()
+                    .filter(|wish| solution[*wish] == *group)
+                    .cloned()
+                    .collect();
+                let best_rank = preference
+                    .wishes
+                    .iter()
+                    .position(|wish| solution[wish] == *group)
+                    .map(|rank| rank + 1);
+                Satisfaction {
+                    student_id: preference.student_id.clone(),
+                    satisfied,
+                    num_wishes: preference.wishes.len(),
+                    best_rank,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::GroupId;
+
+    use super::*;
+
+    #[test]
+    fn test_preference_penalty() {
+        let preferences = Preferences {
+            students: vec![
+                Preference {
+                    student_id: 0.into(),
+                    wishes: vec![1.into(), 2.into()],
+                },
+                Preference {
+                    student_id: 3.into(),
+                    wishes: vec![0.into()],
+                },
+            ],
+            weight: 1.0,
+            guarantee: None,
+        };
+        let mut solution = Solution::new();
+        solution.insert(0.into(), GroupId(0));
+        solution.insert(1.into(), GroupId(1));
+        solution.insert(2.into(), GroupId(0));
+        solution.insert(3.into(), GroupId(1));
+
+        // student 0 misses their first wish, student 3 gets nothing
+        assert_eq!(preferences.penalty(&solution), 4.0);
+        let satisfaction = preferences.satisfaction(&solution);
+        assert_eq!(satisfaction[0].satisfied, vec![StudentId(2)]);
+        assert_eq!(satisfaction[0].best_rank, Some(2));
+        assert_eq!(satisfaction[1].best_rank, None);
+        let isolated: Vec<&StudentId> = preferences
+            .isolated_students(&solution)
+            .map(|(preference, _)| &preference.student_id)
+            .collect();
+        assert_eq!(isolated, vec![&StudentId(3)]);
+    }
+}
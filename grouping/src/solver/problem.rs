@@ -0,0 +1,440 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::solver::{
+    attributes::{AttributeObjective, AttributeTerm, Attributes},
+    constraints::{Coverage, SpreadSet},
+    history::{History, RepeatPenalties},
+    preferences::Preferences,
+    units::Units,
+    GroupId, RelationshipKind, RelationshipPair, StudentId,
+};
+
+/// Inclusive bounds on the number of students in a single group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SizeBounds {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl SizeBounds {
+    pub fn exact(size: usize) -> Self {
+        SizeBounds {
+            min: size,
+            max: size,
+        }
+    }
+
+    pub fn contains(&self, size: usize) -> bool {
+        self.min <= size && size <= self.max
+    }
+
+    /// How many students a group of the given size is short of `min` or over `max`.
+    pub fn distance(&self, size: usize) -> usize {
+        if size < self.min {
+            self.min - size
+        } else {
+            size.saturating_sub(self.max)
+        }
+    }
+}
+
+/// Bounds that keep groups as equal in size as possible: every group holds either
+/// `num_students / num_groups` students or one more.
+pub fn even_group_sizes(num_students: usize, num_groups: usize) -> Vec<SizeBounds> {
+    if num_groups == 0 {
+        return vec![];
+    }
+    let bounds = SizeBounds {
+        min: num_students / num_groups,
+        max: num_students.div_ceil(num_groups),
+    };
+    vec![bounds; num_groups]
+}
+
+/// What to do with the students left over when the class does not divide evenly into groups of
+/// the requested size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemainderPolicy {
+    /// Spread the leftover students over the groups, so some groups are one bigger.
+    #[default]
+    Bigger,
+    /// Add one more group and take students from the others, so some groups are one smaller.
+    Smaller,
+    /// Put the leftover students in one extra, short group.
+    LeaveShort,
+}
+
+/// Splits the students into groups of `group_size`, dealing with any remainder according to the
+/// policy, and returns the size of each group.
+pub fn sizes_for_group_size(
+    num_students: usize,
+    group_size: usize,
+    policy: RemainderPolicy,
+) -> anyhow::Result<Vec<usize>> {
+    if group_size == 0 {
+        return Err(anyhow::anyhow!("group_size must be greater than 0"));
+    }
+    if num_students == 0 {
+        return Err(anyhow::anyhow!("There are no students to group"));
+    }
+    let even_split = |num_groups: usize| {
+        (0..num_groups)
+            .map(|group| num_students / num_groups + usize::from(group < num_students % num_groups))
+            .collect()
+    };
+    let sizes = match policy {
+        RemainderPolicy::Bigger => even_split((num_students / group_size).max(1)),
+        RemainderPolicy::Smaller => even_split(num_students.div_ceil(group_size)),
+        RemainderPolicy::LeaveShort => {
+            let mut sizes = vec![group_size; num_students / group_size];
+            let remainder = num_students % group_size;
+            if remainder > 0 {
+                sizes.push(remainder);
+            }
+            sizes
+        }
+    };
+    Ok(sizes)
+}
+
+/// Fixes a student to a group, or keeps them out of some groups, before solving.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Pin {
+    pub student_id: StudentId,
+    /// The only group the student may be placed in.
+    #[serde(default)]
+    pub group: Option<GroupId>,
+    /// Groups the student must not be placed in.
+    #[serde(default)]
+    pub forbidden_groups: Vec<GroupId>,
+}
+
+/// Everything the solver needs to know about a grouping problem.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub students: Vec<StudentId>,
+    pub num_groups: usize,
+    pub relationship_pairs: Vec<RelationshipPair>,
+    /// Size bounds for each group, indexed by `GroupId`.
+    pub group_sizes: Vec<SizeBounds>,
+    /// Attributes to balance across the groups.
+    pub attribute_terms: Vec<AttributeTerm>,
+    /// The groups each pinned student may be placed in. Students not listed may go anywhere.
+    pub allowed_groups: HashMap<StudentId, Vec<GroupId>>,
+    pub spread_sets: Vec<SpreadSet>,
+    pub coverage: Vec<Coverage>,
+    /// The students carrying each tag.
+    pub tags: HashMap<String, Vec<StudentId>>,
+    /// Penalties for pairs who have shared a group in previous sessions.
+    pub repeat_penalties: RepeatPenalties,
+    /// Partner wishes of the students.
+    pub preferences: Preferences,
+    /// The students joined by hard together pairs, which the solver moves as a whole.
+    pub units: Units<StudentId>,
+}
+
+impl Problem {
+    /// Creates a problem whose groups are as equal in size as possible.
+    pub fn new(
+        students: Vec<StudentId>,
+        num_groups: usize,
+        relationship_pairs: Vec<RelationshipPair>,
+    ) -> Self {
+        let group_sizes = even_group_sizes(students.len(), num_groups);
+        let units = Units::new(
+            &students,
+            relationship_pairs
+                .iter()
+                .filter(|pair| pair.hard && pair.kind == RelationshipKind::Together)
+                .map(|pair| (&pair.first_student_id, &pair.second_student_id)),
+        );
+        Problem {
+            students,
+            num_groups,
+            relationship_pairs,
+            group_sizes,
+            attribute_terms: vec![],
+            allowed_groups: HashMap::new(),
+            spread_sets: vec![],
+            coverage: vec![],
+            tags: HashMap::new(),
+            repeat_penalties: RepeatPenalties::default(),
+            preferences: Preferences::default(),
+            units,
+        }
+    }
+
+    pub fn has_hard_constraints(&self) -> bool {
+        self.relationship_pairs.iter().any(|pair| pair.hard)
+            || self.spread_sets.iter().any(|spread_set| spread_set.hard)
+            || self.coverage.iter().any(|coverage| coverage.hard)
+            || self
+                .preferences
+                .guarantee
+                .as_ref()
+                .is_some_and(|guarantee| guarantee.hard)
+    }
+
+    pub fn tagged_students(&self, tag: &str) -> &[StudentId] {
+        self.tags.get(tag).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true if the student may be placed in the group.
+    pub fn allows(&self, student: &StudentId, group: &GroupId) -> bool {
+        self.allowed_groups
+            .get(student)
+            .is_none_or(|groups| groups.contains(group))
+    }
+
+    /// Returns true if every student of the unit may be placed in the group.
+    pub fn allows_unit(&self, unit: usize, group: &GroupId) -> bool {
+        self.units
+            .members(unit)
+            .iter()
+            .all(|student| self.allows(student, group))
+    }
+
+    /// Replaces the default even group sizes with explicit bounds for each group.
+    pub fn with_group_sizes(mut self, group_sizes: Vec<SizeBounds>) -> anyhow::Result<Self> {
+        if group_sizes.len() != self.num_groups {
+            return Err(anyhow::anyhow!(
+                "Expected {} group sizes but got {}",
+                self.num_groups,
+                group_sizes.len()
+            ));
+        }
+        if let Some(bounds) = group_sizes.iter().find(|bounds| bounds.min > bounds.max) {
+            return Err(anyhow::anyhow!(
+                "Group size minimum {} is greater than maximum {}",
+                bounds.min,
+                bounds.max
+            ));
+        }
+        let min_total: usize = group_sizes.iter().map(|bounds| bounds.min).sum();
+        let max_total: usize = group_sizes.iter().map(|bounds| bounds.max).sum();
+        if !(min_total..=max_total).contains(&self.students.len()) {
+            return Err(anyhow::anyhow!(
+                "Group sizes fit between {} and {} students but there are {}",
+                min_total,
+                max_total,
+                self.students.len()
+            ));
+        }
+        self.group_sizes = group_sizes;
+        Ok(self)
+    }
+
+    /// Adds objective terms that balance, or group students by, the given student attributes.
+    pub fn with_attributes(
+        mut self,
+        attributes: &HashMap<StudentId, Attributes>,
+        objectives: &[AttributeObjective],
+    ) -> anyhow::Result<Self> {
+        if let Some(student) = attributes
+            .keys()
+            .find(|student| !self.students.contains(student))
+        {
+            return Err(anyhow::anyhow!(
+                "Attributes given for unknown student {}",
+                student
+            ));
+        }
+        self.attribute_terms = objectives
+            .iter()
+            .map(|objective| AttributeTerm::new(objective, attributes))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(self)
+    }
+
+    /// Restricts pinned students to their group, and keeps students out of forbidden groups.
+    pub fn with_pins(mut self, pins: &[Pin]) -> anyhow::Result<Self> {
+        let mut allowed_groups: HashMap<StudentId, Vec<GroupId>> = HashMap::new();
+        for pin in pins {
+            if !self.students.contains(&pin.student_id) {
+                return Err(anyhow::anyhow!(
+                    "Pin given for unknown student {}",
+                    pin.student_id
+                ));
+            }
+            if let Some(group) = pin.group.iter().find(|group| ***group >= self.num_groups) {
+                return Err(anyhow::anyhow!(
+                    "Student {} is pinned to group {} but there are only {} groups",
+                    pin.student_id,
+                    group,
+                    self.num_groups
+                ));
+            }
+            if let Some(group) = pin
+                .forbidden_groups
+                .iter()
+                .find(|group| ***group >= self.num_groups)
+            {
+                return Err(anyhow::anyhow!(
+                    "Student {} is forbidden from group {} but there are only {} groups",
+                    pin.student_id,
+                    group,
+                    self.num_groups
+                ));
+            }
+            let groups = allowed_groups
+                .entry(pin.student_id.clone())
+                .or_insert_with(|| (0..self.num_groups).map(GroupId).collect());
+            groups.retain(|group| {
+                pin.group.as_ref().is_none_or(|pinned| pinned == group)
+                    && !pin.forbidden_groups.contains(group)
+            });
+            if groups.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Student {} is not allowed in any group",
+                    pin.student_id
+                ));
+            }
+        }
+        for group in (0..self.num_groups).map(GroupId) {
+            let pinned = allowed_groups
+                .values()
+                .filter(|groups| groups[..] == [group.clone()])
+                .count();
+            if pinned > self.group_sizes[*group].max {
+                return Err(anyhow::anyhow!(
+                    "{} students are pinned to group {} but it holds at most {}",
+                    pinned,
+                    group,
+                    self.group_sizes[*group].max
+                ));
+            }
+        }
+        self.allowed_groups = allowed_groups;
+        Ok(self)
+    }
+
+    /// Adds constraints that limit how many members of each set may share a group. A student
+    /// listed twice in a set counts once.
+    pub fn with_spread_sets(mut self, mut spread_sets: Vec<SpreadSet>) -> anyhow::Result<Self> {
+        for spread_set in &mut spread_sets {
+            remove_repeats(&mut spread_set.students);
+            if let Some(student) = spread_set
+                .students
+                .iter()
+                .find(|student| !self.students.contains(student))
+            {
+                return Err(anyhow::anyhow!(
+                    "Spread set contains unknown student {}",
+                    student
+                ));
+            }
+            if spread_set.max_per_group == 0 {
+                return Err(anyhow::anyhow!(
+                    "Spread set max_per_group must be greater than 0"
+                ));
+            }
+            if !spread_set.weight.is_finite() || spread_set.weight < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "Invalid weight {} in spread set",
+                    spread_set.weight
+                ));
+            }
+        }
+        self.spread_sets = spread_sets;
+        Ok(self)
+    }
+
+    /// Adds constraints that every group holds enough students with a tag. `tags` lists the
+    /// students carrying each tag; a student listed twice for a tag counts once.
+    pub fn with_coverage(
+        mut self,
+        mut tags: HashMap<String, Vec<StudentId>>,
+        coverage: Vec<Coverage>,
+    ) -> anyhow::Result<Self> {
+        if let Some(student) = tags
+            .values()
+            .flatten()
+            .find(|student| !self.students.contains(student))
+        {
+            return Err(anyhow::anyhow!(
+                "Tags given for unknown student {}",
+                student
+            ));
+        }
+        for coverage in &coverage {
+            if coverage.min_per_group == 0 {
+                return Err(anyhow::anyhow!(
+                    "Coverage min_per_group must be greater than 0"
+                ));
+            }
+            if !coverage.weight.is_finite() || coverage.weight < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "Invalid weight {} in coverage for tag {}",
+                    coverage.weight,
+                    coverage.tag
+                ));
+            }
+        }
+        tags.values_mut().for_each(remove_repeats);
+        self.tags = tags;
+        self.coverage = coverage;
+        Ok(self)
+    }
+
+    /// Penalises pairs who have shared a group in previous sessions.
+    pub fn with_history(mut self, history: &History) -> anyhow::Result<Self> {
+        self.repeat_penalties = RepeatPenalties::new(history, &self.students)?;
+        Ok(self)
+    }
+
+    /// Rewards groupings that grant the students' partner wishes.
+    pub fn with_preferences(mut self, preferences: Preferences) -> anyhow::Result<Self> {
+        preferences.validate(&self.students)?;
+        self.preferences = preferences;
+        Ok(self)
+    }
+}
+
+/// Keeps the first of each student listed more than once.
+fn remove_repeats(students: &mut Vec<StudentId>) {
+    let mut seen = HashSet::new();
+    students.retain(|student| seen.insert(student.clone()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes_for_group_size() {
+        let sizes = |num_students, policy| sizes_for_group_size(num_students, 3, policy).unwrap();
+        assert_eq!(sizes(10, RemainderPolicy::Bigger), vec![4, 3, 3]);
+        assert_eq!(sizes(11, RemainderPolicy::Bigger), vec![4, 4, 3]);
+        assert_eq!(sizes(2, RemainderPolicy::Bigger), vec![2]);
+        assert_eq!(sizes(10, RemainderPolicy::Smaller), vec![3, 3, 2, 2]);
+        assert_eq!(sizes(10, RemainderPolicy::LeaveShort), vec![3, 3, 3, 1]);
+        assert_eq!(sizes(9, RemainderPolicy::LeaveShort), vec![3, 3, 3]);
+        assert!(sizes_for_group_size(10, 0, RemainderPolicy::Bigger).is_err());
+    }
+
+    #[test]
+    fn test_spread_set_listing_a_student_twice() {
+        let students: Vec<StudentId> = (0..6).map(StudentId::from).collect();
+        let spread_set = SpreadSet {
+            students: vec![0.into(), 1.into(), 0.into()],
+            max_per_group: 2,
+            weight: 1.0,
+            hard: true,
+        };
+        let problem = Problem::new(students, 2, vec![])
+            .with_spread_sets(vec![spread_set])
+            .unwrap();
+        assert_eq!(problem.spread_sets[0].students, vec![0.into(), 1.into()]);
+    }
+
+    #[test]
+    fn test_tag_listing_a_student_twice() {
+        let students: Vec<StudentId> = (0..6).map(StudentId::from).collect();
+        let tags = HashMap::from([("x".to_string(), vec![0.into(), 0.into(), 1.into()])]);
+        let problem = Problem::new(students, 2, vec![])
+            .with_coverage(tags, vec![])
+            .unwrap();
+        assert_eq!(problem.tagged_students("x"), [0.into(), 1.into()]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Disjoint sets over indices, merged with union by size.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut element: usize) -> usize {
+        while self.parent[element] != element {
+            self.parent[element] = self.parent[self.parent[element]];
+            element = self.parent[element];
+        }
+        element
+    }
+
+    fn union(&mut self, first: usize, second: usize) {
+        let (mut first, mut second) = (self.find(first), self.find(second));
+        if first == second {
+            return;
+        }
+        if self.size[first] < self.size[second] {
+            std::mem::swap(&mut first, &mut second);
+        }
+        self.parent[second] = first;
+        self.size[first] += self.size[second];
+    }
+}
+
+/// Students who must be together, contracted into units that are always placed as a whole. A
+/// student without such a constraint is a unit of their own.
+#[derive(Debug, Clone)]
+pub struct Units<T> {
+    members: Vec<Vec<T>>,
+    unit_of: HashMap<T, usize>,
+}
+
+impl<T: Clone + Eq + Hash> Default for Units<T> {
+    fn default() -> Self {
+        Units {
+            members: vec![],
+            unit_of: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Units<T> {
+    /// Merges the students of each together pair, ignoring pairs with unknown students. Units are
+    /// ordered by their first student.
+    pub fn new<'a>(students: &[T], together: impl IntoIterator<Item = (&'a T, &'a T)>) -> Self
+    where
+        T: 'a,
+    {
+        let index: HashMap<&T, usize> = students
+            .iter()
+            .enumerate()
+            .map(|(i, student)| (student, i))
+            .collect();
+        let mut sets = UnionFind::new(students.len());
+        for (first, second) in together {
+            if let (Some(&first), Some(&second)) = (index.get(first), index.get(second)) {
+                sets.union(first, second);
+            }
+        }
+
+        let mut units: HashMap<usize, usize> = HashMap::new();
+        let mut members: Vec<Vec<T>> = vec![];
+        let mut unit_of = HashMap::new();
+        for (i, student) in students.iter().enumerate() {
+            let unit = *units.entry(sets.find(i)).or_insert_with(|| {
+                members.push(vec![]);
+                members.len() - 1
+            });
+            members[unit].push(student.clone());
+            unit_of.insert(student.clone(), unit);
+        }
+        Units { members, unit_of }
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn members(&self, unit: usize) -> &[T] {
+        &self.members[unit]
+    }
+
+    pub fn unit_of(&self, student: &T) -> usize {
+        self.unit_of[student]
+    }
+
+    /// The pairs of units that must be apart, one for each pair of students that must be apart.
+    /// Fails with the first pair whose students must also be together.
+    pub fn separations<'a>(
+        &self,
+        apart: impl IntoIterator<Item = (&'a T, &'a T)>,
+    ) -> Result<Vec<(usize, usize)>, (T, T)>
+    where
+        T: 'a,
+    {
+        apart
+            .into_iter()
+            .map(|(first, second)| {
+                let (first_unit, second_unit) = (self.unit_of(first), self.unit_of(second));
+                if first_unit == second_unit {
+                    Err((first.clone(), second.clone()))
+                } else {
+                    Ok((first_unit, second_unit))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        let students: Vec<usize> = (0..6).collect();
+        let together = [(0, 1), (4, 1), (2, 5)];
+        let units = Units::new(
+            &students,
+            together.iter().map(|(first, second)| (first, second)),
+        );
+        assert_eq!(units.len(), 3);
+        assert_eq!(units.members(0), &[0, 1, 4]);
+        assert_eq!(units.members(1), &[2, 5]);
+        assert_eq!(units.unit_of(&3), 2);
+
+        assert_eq!(
+            units.separations([(&0, &2), (&3, &5)]),
+            Ok(vec![(0, 1), (2, 1)])
+        );
+        assert_eq!(units.separations([(&0, &2), (&4, &0)]), Err((4, 0)));
+    }
+}
@@ -0,0 +1,197 @@
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::time::Instant;
+use tracing::debug;
+
+use crate::solver::{
+    diagnosis,
+    evaluation::{Move, State},
+    finish, good_enough_score,
+    group_move::{random_solution, within_bounds},
+    Problem, Report, RunLimit, Solver, SCORE_TOLERANCE,
+};
+
+/// How many swaps with units of other groups are looked at for each unit in a step.
+const SWAPS_PER_UNIT: usize = 2;
+
+/// Runs [`tabu_search`] as a [`Solver`].
+#[derive(Debug, Clone, Copy)]
+pub struct TabuSearch {
+    pub tenure: usize,
+}
+
+impl Solver for TabuSearch {
+    fn solve(
+        &self,
+        problem: &Problem,
+        limit: RunLimit,
+        mut rng: &mut dyn RngCore,
+    ) -> anyhow::Result<Report> {
+        tabu_search(problem, self.tenure, limit, &mut rng)
+    }
+
+    fn moves_per_step(&self, problem: &Problem) -> u64 {
+        // each unit may go to any other group, and tries a few swaps
+        (problem.units.len() * (problem.num_groups + SWAPS_PER_UNIT)) as u64
+    }
+}
+
+/// Searches for a good grouping by tabu search. Each step makes the best move or swap of units
+/// that is not tabu, even if it makes the grouping worse. Moving a unit out of a group makes
+/// moving it back tabu for the next `tenure` steps, unless the move would give the best grouping
+/// found so far. Ties between equally good moves are broken at random.
+#[tracing::instrument(skip(rng))]
+pub fn tabu_search(
+    problem: &Problem,
+    tenure: usize,
+    limit: RunLimit,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Report> {
+    diagnosis::diagnose(problem)?;
+    let start = Instant::now();
+    let lower_bound = good_enough_score(problem);
+    let mut state = State::new(problem, &random_solution(problem, rng)?)?;
+
+    let mut best_assignment = state.assignment().to_vec();
+    let mut best_score = state.score();
+    // the step until which moving each unit into each group is tabu
+    let mut tabu_until = vec![vec![0; problem.num_groups]; problem.units.len()];
+
+    let mut step = 0;
+    while limit.progress(step, start).is_some() {
+        if best_score <= lower_bound {
+            debug!("Reached the lower bound after {} steps", step);
+            break;
+        }
+        let mut chosen: Option<(Move, f64)> = None;
+        let mut ties = 0;
+        for candidate in neighbourhood(&state, problem, rng) {
+            let (inverse, delta) = state.apply(&candidate);
+            let score = state.score();
+            state.undo(&inverse, delta);
+            let tabu = candidate
+                .changes
+                .iter()
+                .any(|&(unit, group)| tabu_until[unit][group] > step as usize);
+            // aspiration: a tabu move is still made if it beats the best grouping
+            if tabu && score >= best_score - SCORE_TOLERANCE {
+                continue;
+            }
+            match &chosen {
+                Some((_, chosen_score)) if score > chosen_score + SCORE_TOLERANCE => continue,
+                Some((_, chosen_score)) if score > chosen_score - SCORE_TOLERANCE => {
+                    ties += 1;
+                    if rng.gen_range(0..ties) == 0 {
+                        chosen = Some((candidate, score));
+                    }
+                }
+                _ => {
+                    ties = 1;
+                    chosen = Some((candidate, score));
+                }
+            }
+        }
+        let Some((change, _)) = chosen else {
+            debug!("Every move is tabu after {} steps", step);
+            break;
+        };
+
+        let (inverse, _) = state.apply(&change);
+        for &(unit, group) in &inverse.changes {
+            tabu_until[unit][group] = (step as usize).saturating_add(tenure);
+        }
+        if state.score() < best_score {
+            best_assignment.copy_from_slice(state.assignment());
+            best_score = state.score();
+        }
+        step += 1;
+    }
+    finish(problem, &state.solution_of(&best_assignment))
+}
+
+/// Every move of a unit to another group, and for each unit a few swaps with units of the same
+/// size in random other groups. While the group sizes are within their bounds, only moves that
+/// keep them there are considered. Listing every swap would make each step quadratic in the size
+/// of the class; sampling keeps it linear, and the swaps still let groups at their bounds trade
+/// students.
+fn neighbourhood(state: &State, problem: &Problem, rng: &mut impl Rng) -> Vec<Move> {
+    let group_sizes = state.group_sizes();
+    let bounds = &problem.group_sizes;
+    let keep_bounds = within_bounds(group_sizes, bounds);
+    let num_groups = problem.num_groups;
+    let mut moves = vec![];
+    for from in 0..num_groups {
+        for &unit in state.units_in(from) {
+            let size = state.unit_size(unit);
+            for to in (0..num_groups).filter(|&to| to != from && state.allows(unit, to)) {
+                if !keep_bounds
+                    || (group_sizes[from] >= bounds[from].min + size
+                        && group_sizes[to] + size <= bounds[to].max)
+                {
+                    moves.push(Move::unit(unit, to));
+                }
+            }
+            for _ in 0..SWAPS_PER_UNIT {
+                let to = rng.gen_range(0..num_groups);
+                let Some(&other) = state.units_in(to).choose(rng) else {
+                    continue;
+                };
+                if to != from
+                    && state.unit_size(other) == size
+                    && state.allows(unit, to)
+                    && state.allows(other, from)
+                {
+                    moves.push(Move::unit(unit, to).and(Move::unit(other, from)));
+                }
+            }
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::solver::{problem::even_group_sizes, RelationshipKind, RelationshipPair, StudentId};
+
+    use super::*;
+
+    fn apart(first: usize, second: usize, hard: bool) -> RelationshipPair {
+        RelationshipPair::new(
+            first.into(),
+            second.into(),
+            RelationshipKind::Apart,
+            1.0,
+            hard,
+        )
+    }
+
+    #[test]
+    fn test_tabu_search() {
+        let students: Vec<StudentId> = (0..21).map(StudentId::from).collect();
+        // a ring of students who must not share a group with their neighbours
+        let pairs = (0..21)
+            .map(|i| apart(i, (i + 1) % 21, i % 2 == 0))
+            .collect();
+        let problem = Problem::new(students, 4, pairs);
+        let solve = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            tabu_search(&problem, 10, RunLimit::steps(200), &mut rng).unwrap()
+        };
+        let result = solve(3);
+        assert!(result.violations.is_empty());
+        let group_sizes: Vec<usize> = result.groups.iter().map(Vec::len).collect();
+        assert!(within_bounds(&group_sizes, &even_group_sizes(21, 4)));
+        assert_eq!(solve(3).groups, result.groups);
+    }
+
+    #[test]
+    fn test_tabu_search_larger_class() {
+        let students: Vec<StudentId> = (0..150).map(StudentId::from).collect();
+        let pairs = (0..150).map(|i| apart(i, (i + 1) % 150, true)).collect();
+        let problem = Problem::new(students, 6, pairs);
+        let mut rng = StdRng::seed_from_u64(1);
+        let result = tabu_search(&problem, 10, RunLimit::steps(300), &mut rng).unwrap();
+        assert!(result.violations.is_empty());
+    }
+}